@@ -1,18 +1,22 @@
 // use std::collections::VecDeque;
+use super::lines::ThreatCell;
+use super::mcts::{default_threads, Cursor, MctsConfig, Proof, Score, Tree};
+use super::mcts_graph::Graph;
+use super::search::{Budget, Searcher};
+use super::tss::ThreatSpaceSearch;
 use proconio::input;
 use rand::Rng;
 use std::fmt;
 use std::ops::Deref;
+use std::str::FromStr;
+use std::time::Duration;
 use std::{
-    borrow::BorrowMut,
-    cell::{Ref, RefCell},
-    collections::{HashMap, HashSet},
     ops::DerefMut,
-    sync::Mutex,
+    sync::{Arc, Mutex},
 };
 use tauri::State;
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Player {
     White,
     Black,
@@ -51,56 +55,497 @@ impl Player {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum GameResult {
+    Ongoing,
+    Won(Player),
+    Draw,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum MoveError {
+    OutOfRange(u8),
+    ColumnFull(u8),
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::OutOfRange(action) => write!(f, "action {} is out of range (0-15)", action),
+            MoveError::ColumnFull(action) => write!(f, "column {} is full", action),
+            MoveError::GameOver => write!(f, "game is already over"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ParseBoardError {
+    InvalidLength(usize),
+    InvalidChar(char),
+    InvalidNumber(String),
+    Overlap,
+    StoneCount { black: u32, white: u32 },
+    Floating(u8),
+    // both sides have a line, or the side to move has one its opponent let stand
+    BothWon,
+    WonBySideToMove,
+    InvalidCell(Cell),
+    Move(MoveError),
+}
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseBoardError::InvalidLength(len) => {
+                write!(f, "expected 65 characters, got {}", len)
+            }
+            ParseBoardError::InvalidChar(c) => write!(f, "unexpected character {:?}", c),
+            ParseBoardError::InvalidNumber(token) => write!(f, "invalid number {:?}", token),
+            ParseBoardError::Overlap => write!(f, "black and white stones overlap"),
+            ParseBoardError::StoneCount { black, white } => write!(
+                f,
+                "{} black and {} white stones do not match the side to move",
+                black, white
+            ),
+            ParseBoardError::Floating(cell) => write!(f, "stone at {} has nothing under it", cell),
+            ParseBoardError::BothWon => write!(f, "both sides have a completed line"),
+            ParseBoardError::WonBySideToMove => {
+                write!(f, "the side to move already has a completed line")
+            }
+            ParseBoardError::InvalidCell(cell) => write!(
+                f,
+                "cell ({}, {}, {}) is outside the board",
+                cell.layer, cell.row, cell.column
+            ),
+            ParseBoardError::Move(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseBoardError {}
+
+impl From<MoveError> for ParseBoardError {
+    fn from(e: MoveError) -> Self {
+        return ParseBoardError::Move(e);
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Cell {
+    pub layer: u8,
+    pub row: u8,
+    pub column: u8,
+}
+
+impl Cell {
+    pub fn from_index(idx: u8) -> Self {
+        return Cell {
+            layer: idx / 16,
+            row: (idx / 4) % 4,
+            column: idx % 4,
+        };
+    }
+
+    pub fn index(&self) -> u8 {
+        return self.layer * 16 + self.row * 4 + self.column;
+    }
+
+    pub fn action(&self) -> u8 {
+        return self.row * 4 + self.column;
+    }
+
+    pub fn is_valid(&self) -> bool {
+        return self.layer < 4 && self.row < 4 && self.column < 4;
+    }
+}
+
+// JSON form of a board: stones as cells plus the side to move
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BoardRepr {
+    black: Vec<Cell>,
+    white: Vec<Cell>,
+    player: Player,
+}
+
+impl From<Board> for BoardRepr {
+    fn from(board: Board) -> Self {
+        let to_cells = |mask: u64| -> Vec<Cell> {
+            mask_to_cells(mask)
+                .into_iter()
+                .map(Cell::from_index)
+                .collect()
+        };
+        return BoardRepr {
+            black: to_cells(board.black),
+            white: to_cells(board.white),
+            player: board.player,
+        };
+    }
+}
+
+impl TryFrom<BoardRepr> for Board {
+    type Error = ParseBoardError;
+
+    fn try_from(repr: BoardRepr) -> Result<Self, Self::Error> {
+        let to_mask = |cells: &Vec<Cell>| -> Result<u64, ParseBoardError> {
+            let mut mask = 0u64;
+            for cell in cells {
+                if !cell.is_valid() {
+                    return Err(ParseBoardError::InvalidCell(*cell));
+                }
+                mask |= 1 << cell.index();
+            }
+            return Ok(mask);
+        };
+        let (black, white) = (to_mask(&repr.black)?, to_mask(&repr.white)?);
+        let board = Board::from_bitboards(black, white)?;
+        if board.player != repr.player {
+            return Err(ParseBoardError::StoneCount {
+                black: black.count_ones(),
+                white: white.count_ones(),
+            });
+        }
+        return Ok(board);
+    }
+}
+
+// no stone was placed, see Board::next
+const NO_CELL: u8 = 64;
+
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
+}
+
+// the 8 planar symmetries, index 0 being the identity. Of the 192 line-preserving symmetries
+// of the cube these are the only ones that keep every stone on its layer, so they are all
+// that gravity leaves
+const fn planar_cell(sym: usize, cell: usize) -> usize {
+    let (l, mut r, mut c) = (cell / 16, (cell / 4) % 4, cell % 4);
+    if sym & 1 == 1 {
+        (r, c) = (c, r);
+    }
+    if sym & 2 == 2 {
+        r = 3 - r;
+    }
+    if sym & 4 == 4 {
+        c = 3 - c;
+    }
+    return l * 16 + r * 4 + c;
+}
+
+// the column a planar symmetry maps `action` to
+pub fn planar_action(sym: usize, action: u8) -> u8 {
+    return planar_cell(sym, action as usize) as u8;
+}
+
+// ZOBRIST[player][cell][sym] is the key of the stone after applying planar symmetry sym
+const ZOBRIST: [[[u64; 8]; 64]; 2] = build_zobrist();
+const ZOBRIST_SIDE: u64 = splitmix64(128);
+
+const fn build_zobrist() -> [[[u64; 8]; 64]; 2] {
+    let mut table = [[[0u64; 8]; 64]; 2];
+    let mut p = 0;
+    while p < 2 {
+        let mut cell = 0;
+        while cell < 64 {
+            let mut sym = 0;
+            while sym < 8 {
+                table[p][cell][sym] = splitmix64((p * 64 + planar_cell(sym, cell)) as u64);
+                sym += 1;
+            }
+            cell += 1;
+        }
+        p += 1;
+    }
+    return table;
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "BoardRepr", into = "BoardRepr")]
 pub struct Board {
     pub black: u64,
     pub white: u64,
     player: Player,
+    // cells placed by make(), undone by unmake(); not part of the position
+    stack: [u8; 64],
+    stack_len: u8,
+    // Zobrist keys of the board under each planar symmetry, keys[0] untransformed
+    keys: [u64; 8],
+    // the player who just moved has completed a line
+    won: bool,
+}
+
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        return self.black == other.black
+            && self.white == other.white
+            && self.player == other.player;
+    }
 }
 
+impl Eq for Board {}
+
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.black.hash(state);
+        self.white.hash(state);
+        self.player.hash(state);
+    }
+}
+
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Board")
+            .field("black", &format_args!("{:#018x}", self.black))
+            .field("white", &format_args!("{:#018x}", self.white))
+            .field("player", &self.player)
+            .finish()
+    }
+}
+
+// iterates the non-full columns of a board, lowest action first
+#[derive(Clone, Copy)]
+pub struct Actions {
+    mask: u16,
+}
+
+impl Iterator for Actions {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.mask == 0 {
+            return None;
+        }
+        let action = self.mask.trailing_zeros() as u8;
+        self.mask &= self.mask - 1;
+        return Some(action);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.mask.count_ones() as usize;
+        return (n, Some(n));
+    }
+}
+
+impl ExactSizeIterator for Actions {}
+
 impl Board {
     pub fn new() -> Self {
         return Board {
             black: 0,
             white: 0,
             player: Player::Black,
+            stack: [0; 64],
+            stack_len: 0,
+            keys: [0; 8],
+            won: false,
+        };
+    }
+
+    // only the lines through the last stone can have been completed by it
+    fn refresh_won(&mut self) {
+        let stones = self.stones(&self.player.next());
+        self.won = match self.last_cell() {
+            Some(cell) => is_win_through(stones, cell),
+            None => _is_win_board(stones),
         };
     }
+
+    fn toggle_key(&mut self, player: &Player, cell: u8) {
+        let table = &ZOBRIST[player.clone() as usize][cell as usize];
+        for sym in 0..8 {
+            self.keys[sym] ^= table[sym] ^ ZOBRIST_SIDE;
+        }
+    }
+
+    pub fn key(&self) -> u64 {
+        return self.keys[0];
+    }
+
+    // equal for positions that are planar symmetric to each other
+    pub fn canonical_key(&self) -> u64 {
+        return *self.keys.iter().min().unwrap();
+    }
+
+    // the planar symmetry, numbered as for planar_action, that maps this board onto `other`
+    pub fn symmetry_to(&self, other: &Board) -> Option<usize> {
+        return (0..8).find(|sym| self.keys[*sym] == other.keys[0]);
+    }
+
+    // the caller must pass an action from valid_actions(); use try_next() for untrusted input
     pub fn next(&self, action_id: u8) -> Self {
+        let mut board = self.clone();
+        board.make(action_id);
+        return board;
+    }
+
+    // in-place next(). An illegal action is a bug in the caller and panics, in release builds
+    // too, rather than silently passing the turn
+    pub fn make(&mut self, action_id: u8) {
+        assert!(self.is_legal(action_id), "illegal action {}", action_id);
+        assert!(
+            (self.stack_len as usize) < self.stack.len(),
+            "move stack is full"
+        );
         let board = self.black | self.white;
         let action_bitboard: u64 =
             (0x0001000100010001u64 << action_id) & ((!board << 16) ^ (!board));
         match self.player {
-            Player::Black => Board {
-                black: self.black | action_bitboard,
-                white: self.white,
-                player: Player::White,
-            },
-            Player::White => Board {
-                black: self.black,
-                white: self.white | action_bitboard,
-                player: Player::Black,
-            },
+            Player::Black => self.black |= action_bitboard,
+            Player::White => self.white |= action_bitboard,
         }
+        let cell = if action_bitboard == 0 {
+            for sym in 0..8 {
+                self.keys[sym] ^= ZOBRIST_SIDE;
+            }
+            NO_CELL
+        } else {
+            let cell = action_bitboard.trailing_zeros() as u8;
+            self.toggle_key(&self.player.clone(), cell);
+            cell
+        };
+        self.player = self.player.next();
+        self.stack[self.stack_len as usize] = cell;
+        self.stack_len += 1;
+        self.refresh_won();
     }
 
-    pub fn is_draw(&self) -> bool {
-        return (self.black | self.white) == 0xffffffffffffffff;
+    // takes back the last make(); returns false when there is nothing to undo
+    pub fn unmake(&mut self) -> bool {
+        if self.stack_len == 0 {
+            return false;
+        }
+        self.stack_len -= 1;
+        self.player = self.player.next();
+        let cell = self.stack[self.stack_len as usize];
+        if cell != NO_CELL {
+            match self.player {
+                Player::Black => self.black &= !(1 << cell),
+                Player::White => self.white &= !(1 << cell),
+            }
+            self.toggle_key(&self.player.clone(), cell);
+        } else {
+            for sym in 0..8 {
+                self.keys[sym] ^= ZOBRIST_SIDE;
+            }
+        }
+        self.refresh_won();
+        return true;
     }
 
-    pub fn is_win(&self) -> bool {
-        match self.player {
-            Player::White => _is_win_board(self.black),
-            Player::Black => _is_win_board(self.white),
+    // the same stones with the other side to move, as if the turn were passed; the history is
+    // dropped so searches from here have the full stack
+    pub fn null_move(&self) -> Board {
+        let mut b = self.clone();
+        b.player = b.player.next();
+        for sym in 0..8 {
+            b.keys[sym] ^= ZOBRIST_SIDE;
         }
+        b.stack_len = 0;
+        b.refresh_won();
+        return b;
     }
 
-    pub fn clone(&self) -> Self {
-        return Board {
-            black: self.black,
-            white: self.white,
-            player: self.player.clone(),
-        };
+    // the actions made since the board was built, passes left out
+    pub fn moves(&self) -> Vec<u8> {
+        return self.stack[..self.stack_len as usize]
+            .iter()
+            .filter(|cell| **cell != NO_CELL)
+            .map(|cell| cell % 16)
+            .collect();
+    }
+
+    // the cell filled by the last make(), if it is still on the stack
+    pub fn last_cell(&self) -> Option<u8> {
+        if self.stack_len == 0 {
+            return None;
+        }
+        let cell = self.stack[self.stack_len as usize - 1];
+        if cell == NO_CELL {
+            return None;
+        }
+        return Some(cell);
+    }
+
+    pub fn try_next(&self, action_id: u8) -> Result<Self, MoveError> {
+        if action_id >= 16 {
+            return Err(MoveError::OutOfRange(action_id));
+        }
+        if self.result() != GameResult::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+        if !self.is_legal(action_id) {
+            return Err(MoveError::ColumnFull(action_id));
+        }
+        return Ok(self.next(action_id));
+    }
+
+    // only the player who just moved can have completed a line
+    pub fn result(&self) -> GameResult {
+        if self.won {
+            return GameResult::Won(self.player.next());
+        } else if self.is_full() {
+            return GameResult::Draw;
+        }
+        return GameResult::Ongoing;
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        match self.result() {
+            GameResult::Won(player) => Some(player),
+            _ => None,
+        }
+    }
+
+    pub fn is_won_by(&self, player: &Player) -> bool {
+        return _is_win_board(self.stones(player));
+    }
+
+    pub fn winning_lines(&self, player: &Player) -> Vec<u64> {
+        let stones = self.stones(player);
+        return LINES
+            .iter()
+            .filter(|line| stones & **line == **line)
+            .copied()
+            .collect();
+    }
+
+    // cells of every line completed by the winner, empty while the game is undecided
+    pub fn winning_cells(&self) -> Vec<u8> {
+        match self.winner() {
+            Some(player) => mask_to_cells(self.winning_lines(&player).iter().fold(0, |a, l| a | l)),
+            None => vec![],
+        }
+    }
+
+    pub fn stones(&self, player: &Player) -> u64 {
+        match player {
+            Player::Black => self.black,
+            Player::White => self.white,
+        }
+    }
+
+    pub fn player(&self) -> Player {
+        return self.player.clone();
+    }
+
+    pub fn is_full(&self) -> bool {
+        return (self.black | self.white) == 0xffffffffffffffff;
+    }
+
+    pub fn is_draw(&self) -> bool {
+        return self.result() == GameResult::Draw;
+    }
+
+    // true when the player who just moved has won
+    pub fn is_win(&self) -> bool {
+        return self.won;
     }
 
     pub fn action_mask(&self) -> u64 {
@@ -108,88 +553,149 @@ impl Board {
         return board >> 48;
     }
 
-    pub fn valid_actions(&self) -> Vec<u8> {
-        let mut actions = Vec::<u8>::new();
-        let board = (self.black | self.white) >> 48;
-        for i in 0..16u8 {
-            if (board >> i) & 1 == 0 {
-                actions.push(i);
-            }
+    // the column exists and is not full; says nothing about whether the game is over
+    pub fn is_legal(&self, action_id: u8) -> bool {
+        return action_id < 16 && (self.action_mask() >> action_id) & 1 == 0;
+    }
+
+    // layer the stone would land on, None when the column is full
+    pub fn landing_layer(&self, action_id: u8) -> Option<u8> {
+        let column = (self.black | self.white) & (0x0001000100010001u64 << action_id);
+        let height = column.count_ones() as u8;
+        if height < 4 {
+            return Some(height);
         }
+        return None;
+    }
+
+    pub fn actions(&self) -> Actions {
+        return Actions {
+            mask: !(self.action_mask() as u16),
+        };
+    }
 
-        return actions;
+    pub fn valid_actions(&self) -> Vec<u8> {
+        return self.actions().collect();
+    }
+
+    pub fn random_action<R: Rng>(&self, rng: &mut R) -> u8 {
+        let mask = !(self.action_mask() as u16);
+        let mut rest = mask;
+        for _ in 0..rng.gen_range(0..mask.count_ones()) {
+            rest &= rest - 1;
+        }
+        return rest.trailing_zeros() as u8;
     }
 
     pub fn has_mate(&self, depth: u8) -> (bool, u8) {
-        if depth == 1 {
-            for action in self.valid_actions() {
-                let next_board = self.next(action);
-                if next_board.is_win() {
-                    return (true, action);
-                }
-            }
-        } else {
-            for action in self.valid_actions() {
-                let next_board = self.next(action);
-                if next_board.is_win() {
-                    return (true, action);
-                } else {
-                    let val = -next_board._minimax_action(depth - 1);
-                    if val == 1 {
-                        return (true, action);
-                    }
-                }
-            }
+        match Searcher::for_depth(depth).find_mate(self, depth) {
+            Some((_, action)) => (true, action),
+            None => (false, 0),
         }
-        return (false, 0);
     }
 
     pub fn minimax_action(&self, depth: u8) -> u8 {
-        let mut rng = rand::thread_rng();
-        if depth == 1 {
-            let actions = self.valid_actions();
-            for action in actions.iter() {
-                let next_board = self.next(*action);
-                if next_board.is_win() {
-                    return *action;
-                } else if next_board.is_draw() {
-                    return *action;
-                }
-            }
-            return actions[rng.gen::<usize>() % actions.len()];
+        return Searcher::for_depth(depth).search(self, depth).action;
+    }
+
+    pub fn to_string(&self) -> String {
+        return format!("{},{}", self.black, self.white);
+    }
+
+    // the side to move follows from the stone counts, Black moving first
+    pub fn from_bitboards(black: u64, white: u64) -> Result<Self, ParseBoardError> {
+        if black & white != 0 {
+            return Err(ParseBoardError::Overlap);
+        }
+        let (nb, nw) = (black.count_ones(), white.count_ones());
+        let player = if nb == nw {
+            Player::Black
+        } else if nb == nw + 1 {
+            Player::White
         } else {
-            let mut actions: Vec<u8> = Vec::new();
-            let mut max_val: i8 = -2;
-            for action in self.valid_actions() {
-                let next_board = self.next(action);
-                if next_board.is_win() {
-                    return action;
-                } else if next_board.is_draw() {
-                    return action;
-                } else {
-                    let val = -next_board._minimax_action(depth - 1);
-                    if val == 1 {
-                        return action;
-                    }
-                    if max_val < val {
-                        max_val = val;
-                        actions = vec![action];
-                    } else if max_val == val {
-                        actions.push(action);
-                    }
-                }
+            return Err(ParseBoardError::StoneCount {
+                black: nb,
+                white: nw,
+            });
+        };
+        let board = black | white;
+        let floating = (board >> 16) & !board;
+        if floating != 0 {
+            return Err(ParseBoardError::Floating(
+                floating.trailing_zeros() as u8 + 16,
+            ));
+        }
+        match (_is_win_board(black), _is_win_board(white)) {
+            (true, true) => return Err(ParseBoardError::BothWon),
+            (true, false) if player == Player::Black => {
+                return Err(ParseBoardError::WonBySideToMove)
             }
+            (false, true) if player == Player::White => {
+                return Err(ParseBoardError::WonBySideToMove)
+            }
+            _ => {}
+        }
+        let mut b = Board {
+            black: black,
+            white: white,
+            player: player,
+            stack: [0; 64],
+            stack_len: 0,
+            keys: [0; 8],
+            won: false,
+        };
+        b.refresh_won();
+        // each stone flips the side key once, which leaves it set exactly when White is to move
+        for cell in mask_to_cells(black) {
+            b.toggle_key(&Player::Black, cell);
+        }
+        for cell in mask_to_cells(white) {
+            b.toggle_key(&Player::White, cell);
+        }
+        return Ok(b);
+    }
 
-            // println!("actions:{:#?}, val:{}", actions, max_val);
-            return actions[rng.gen::<usize>() % actions.len()];
+    pub fn from_board_string(s: &str) -> Result<Self, ParseBoardError> {
+        let chars: Vec<char> = s.trim().chars().collect();
+        if chars.len() != 65 {
+            return Err(ParseBoardError::InvalidLength(chars.len()));
         }
+        let (mut black, mut white) = (0u64, 0u64);
+        for i in 0..64 {
+            match chars[i] {
+                'O' => black |= 1 << i,
+                'X' => white |= 1 << i,
+                '-' => {}
+                c => return Err(ParseBoardError::InvalidChar(c)),
+            }
+        }
+        let board = Board::from_bitboards(black, white)?;
+        let player = match chars[64] {
+            'B' => Player::Black,
+            'W' => Player::White,
+            c => return Err(ParseBoardError::InvalidChar(c)),
+        };
+        if board.player != player {
+            return Err(ParseBoardError::StoneCount {
+                black: black.count_ones(),
+                white: white.count_ones(),
+            });
+        }
+        return Ok(board);
     }
 
-    fn to_string(&self) -> String {
-        return format!("{},{}", self.black, self.white);
+    pub fn from_moves(s: &str) -> Result<Self, ParseBoardError> {
+        let mut board = Board::new();
+        for token in s.split_whitespace() {
+            let action = token
+                .parse::<u8>()
+                .map_err(|_| ParseBoardError::InvalidNumber(String::from(token)))?;
+            board = board.try_next(action)?;
+        }
+        return Ok(board);
     }
 
-    fn to_board_string(&self) -> String {
+    pub fn to_board_string(&self) -> String {
         let mut s = String::new();
         for i in 0..64 {
             if (self.black >> i) & 1 == 1 {
@@ -207,26 +713,6 @@ impl Board {
         return s;
     }
 
-    fn _minimax_action(&self, depth: u8) -> i8 {
-        if depth == 0 {
-            return 0;
-        }
-        let mut max_val = -2;
-        for action in self.valid_actions() {
-            let next_board = self.next(action);
-            if next_board.is_win() {
-                return 1;
-            } else if next_board.is_draw() {
-                return 0;
-            }
-            let val = -next_board._minimax_action(depth - 1);
-            if max_val < val {
-                max_val = val;
-            }
-        }
-        return max_val;
-    }
-
     pub fn is_black(&self) -> bool {
         match self.player {
             Player::Black => true,
@@ -238,6 +724,7 @@ impl Board {
         return (self.black as u128) + ((self.white as u128) << 64);
     }
 
+    // smallest to_u128 image under the planar symmetries
     pub fn hash(&self) -> u128 {
         let mut bitboard = self.to_u128();
         let mut min_bitboard = bitboard;
@@ -283,6 +770,94 @@ impl Board {
     }
 }
 
+// cell index is layer * 16 + row * 4 + column, the layer being the height
+pub const LINES: [u64; 76] = build_lines();
+
+const fn build_lines() -> [u64; 76] {
+    let mut lines = [0u64; 76];
+    let mut count = 0;
+    let mut d = 0;
+    while d < 27 {
+        let (dl, dr, dc) = (d / 9 - 1, (d / 3) % 3 - 1, d % 3 - 1);
+        // keep one of each pair of opposite directions
+        let forward = dl > 0 || (dl == 0 && (dr > 0 || (dr == 0 && dc > 0)));
+        let mut cell = 0;
+        while forward && cell < 64 {
+            let (l, r, c) = (cell / 16, (cell / 4) % 4, cell % 4);
+            let (el, er, ec) = (l + 3 * dl, r + 3 * dr, c + 3 * dc);
+            let starts = (dl == 0 || l == if dl > 0 { 0 } else { 3 })
+                && (dr == 0 || r == if dr > 0 { 0 } else { 3 })
+                && (dc == 0 || c == if dc > 0 { 0 } else { 3 });
+            if starts && el >= 0 && el < 4 && er >= 0 && er < 4 && ec >= 0 && ec < 4 {
+                let mut line = 0u64;
+                let mut k = 0;
+                while k < 4 {
+                    line |= 1 << ((l + k * dl) * 16 + (r + k * dr) * 4 + (c + k * dc));
+                    k += 1;
+                }
+                lines[count] = line;
+                count += 1;
+            }
+            cell += 1;
+        }
+        d += 1;
+    }
+    return lines;
+}
+
+// CELL_LINES[cell] holds the 4 or 7 lines through the cell, padded with zeros
+pub const CELL_LINES: [[u64; 7]; 64] = build_cell_lines();
+
+const fn build_cell_lines() -> [[u64; 7]; 64] {
+    let mut table = [[0u64; 7]; 64];
+    let mut cell = 0;
+    while cell < 64 {
+        let mut count = 0;
+        let mut i = 0;
+        while i < 76 {
+            if (LINES[i] >> cell) & 1 == 1 {
+                table[cell][count] = LINES[i];
+                count += 1;
+            }
+            i += 1;
+        }
+        cell += 1;
+    }
+    return table;
+}
+
+// whether the stones complete one of the lines through cell
+pub fn is_win_through(stones: u64, cell: u8) -> bool {
+    for line in CELL_LINES[cell as usize].iter() {
+        if *line != 0 && stones & line == *line {
+            return true;
+        }
+    }
+    return false;
+}
+
+pub fn mask_to_cells(mask: u64) -> Vec<u8> {
+    return (0..64u8).filter(|i| (mask >> i) & 1 == 1).collect();
+}
+
+// accepts the 65-character board string, "black,white" bitboards or a move list
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() == 65 && s.ends_with(|c| c == 'B' || c == 'W') {
+            return Board::from_board_string(s);
+        }
+        if let Some((black, white)) = s.split_once(',') {
+            if let (Ok(black), Ok(white)) = (black.trim().parse(), white.trim().parse()) {
+                return Board::from_bitboards(black, white);
+            }
+        }
+        return Board::from_moves(s);
+    }
+}
+
 pub fn _is_win_board(bit: u64) -> bool {
     (bit & (bit >> 1) & (bit >> 2) & (bit >> 3) & 0x1111111111111111)
         | (bit & (bit >> 4) & (bit >> 8) & (bit >> 12) & 0x000f000f000f000f)
@@ -301,9 +876,7 @@ pub fn _is_win_board(bit: u64) -> bool {
 }
 
 pub fn get_random(board: &Board) -> u8 {
-    let mut rng = rand::thread_rng();
-    let actions = board.valid_actions();
-    return actions[rng.gen::<usize>() % actions.len()];
+    return board.random_action(&mut rand::thread_rng());
 }
 
 pub fn pprint_board(board: &Board) {
@@ -327,59 +900,460 @@ pub fn pprint_board(board: &Board) {
     print!("{}", s);
 }
 
-fn playout(board: &Board) -> f32 {
+// value of a random game for the side to move on `board`
+// the cells the playout fills are appended to `cells`, starting with the side to move
+pub fn playout(board: &Board, cells: &mut Vec<u8>) -> f32 {
+    let mut rng = rand::thread_rng();
+    let player = board.player();
     let mut b = board.clone();
-    let mut coef = 1.0;
     loop {
-        let action = get_random(&b);
-        b = b.next(action);
-        if b.is_win() {
-            return coef;
-        } else if b.is_draw() {
-            return 0.0;
+        let action = b.random_action(&mut rng);
+        b.make(action);
+        cells.push(b.last_cell().unwrap());
+        match b.result() {
+            GameResult::Won(winner) if winner == player => return 1.0,
+            GameResult::Won(_) => return -1.0,
+            GameResult::Draw => return 0.0,
+            GameResult::Ongoing => {}
         }
-        coef *= -1.0;
     }
 }
 
-#[derive(serde::Serialize)]
+// a forced win: the attacker's move and their answer to every defender reply
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MateNode {
+    pub action: u8,
+    // plies to the win, counting this move
+    pub depth: u8,
+    // empty when this move wins on the spot
+    pub replies: Vec<MateReply>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MateReply {
+    pub action: u8,
+    pub answer: MateNode,
+}
+
+impl MateNode {
+    // the attacker's moves against the longest defence, alternating with the defender's
+    pub fn principal_line(&self) -> Vec<u8> {
+        let mut line = vec![self.action];
+        let mut node = self;
+        while let Some(reply) = node.replies.iter().max_by_key(|r| r.answer.depth) {
+            line.push(reply.action);
+            line.push(reply.answer.action);
+            node = &reply.answer;
+        }
+        return line;
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct MateRow {
     depth: i32,
     action: i32,
+    status: ProofStatus,
+    line: Vec<u8>,
+    tree: Option<MateNode>,
+}
+
+impl MateRow {
+    fn none(status: ProofStatus) -> Self {
+        return MateRow {
+            depth: 0,
+            action: -1,
+            status: status,
+            line: Vec::new(),
+            tree: None,
+        };
+    }
+
+    fn proven(tree: MateNode) -> Self {
+        return MateRow {
+            depth: tree.depth as i32,
+            action: tree.action as i32,
+            status: ProofStatus::Proven,
+            line: tree.principal_line(),
+            tree: Some(tree),
+        };
+    }
 }
 
 fn _search_mate(b: &Board, depth_max: u8) -> MateRow {
+    // the table is shared between depths so each iteration starts from the last
+    let mut searcher = Searcher::for_depth(depth_max);
     for i in 0..=((depth_max - 1) / 2) {
         let depth = i * 2 + 1;
         println!("depth: {} start", depth);
-        let (flag, action) = b.has_mate(depth);
-        if flag {
-            return MateRow {
-                depth: depth as i32,
-                action: action as i32,
-            };
+        if let Some(tree) = searcher.mate_tree(b, depth) {
+            return MateRow::proven(tree);
         }
         println!("depth: {} end", depth);
     }
-    return MateRow {
-        depth: 0,
-        action: -1,
+    return MateRow::none(ProofStatus::Unknown);
+}
+
+fn _search_mate_timed(b: &Board, budget: Budget) -> MateRow {
+    let mut searcher = Searcher::for_budget(budget);
+    let result = searcher.iterative(b, budget);
+    match result
+        .mate_in()
+        .and_then(|plies| searcher.mate_tree(b, plies))
+    {
+        Some(tree) => MateRow::proven(tree),
+        None => MateRow::none(ProofStatus::Unknown),
+    }
+}
+
+fn _search_mate_pns(b: &Board, limit: PnsLimit) -> MateRow {
+    let result = b.proof_search(limit);
+    match result.tree {
+        Some(tree) => MateRow::proven(tree),
+        None => MateRow::none(result.status),
+    }
+}
+
+fn _search_mate_tss(b: &Board, node_limit: u64) -> MateRow {
+    let mut tss = ThreatSpaceSearch::new(node_limit);
+    let tree = tss.solve(b, 64);
+    match tree {
+        Some(tree) => MateRow::proven(tree),
+        None => MateRow::none(ProofStatus::Unknown),
+    }
+}
+
+// what a move of the side to move leaves the opponent
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Danger {
+    // the opponent has no forced win, or the move ends the game
+    Safe,
+    // the opponent wins on their n-th ply at the latest
+    Losing(u8),
+    Unknown,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ColumnDanger {
+    action: u8,
+    danger: Danger,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DangerReport {
+    // the opponent's forced win if they were to move now
+    threat: MateRow,
+    columns: Vec<ColumnDanger>,
+}
+
+impl Board {
+    pub fn danger(&self, action: u8, limit: PnsLimit) -> Danger {
+        let b = self.next(action);
+        if b.result() != GameResult::Ongoing {
+            return Danger::Safe;
+        }
+        let result = b.proof_search(limit);
+        match (result.status, result.distance) {
+            (ProofStatus::Proven, Some(plies)) => Danger::Losing(plies),
+            (ProofStatus::Disproven, _) => Danger::Safe,
+            _ => Danger::Unknown,
+        }
+    }
+}
+
+fn _analyze_danger(b: &Board, limit: PnsLimit) -> DangerReport {
+    if b.result() != GameResult::Ongoing {
+        return DangerReport {
+            threat: MateRow::none(ProofStatus::Disproven),
+            columns: Vec::new(),
+        };
+    }
+    let threat = _search_mate_pns(&b.null_move(), limit);
+    let columns = b
+        .valid_actions()
+        .into_iter()
+        .map(|action| ColumnDanger {
+            action: action,
+            danger: b.danger(action, limit),
+        })
+        .collect();
+    return DangerReport {
+        threat: threat,
+        columns: columns,
     };
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProofStatus {
+    // the side to move can force a win
+    Proven,
+    // the side to move cannot force a win
+    Disproven,
+    // the limit was reached first
+    Unknown,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PnsLimit {
+    Nodes(usize),
+    Memory(usize),
+}
+
+impl PnsLimit {
+    fn max_nodes(&self) -> usize {
+        match self {
+            PnsLimit::Nodes(nodes) => *nodes,
+            PnsLimit::Memory(bytes) => bytes / std::mem::size_of::<PnNode>(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProofResult {
+    pub status: ProofStatus,
+    // plies to the win along the proof tree, which need not be the shortest mate
+    pub distance: Option<u8>,
+    pub action: Option<u8>,
+    pub tree: Option<MateNode>,
+    pub nodes: usize,
+}
+
+const PN_INF: u32 = u32::MAX;
+
+// the board is not stored; it is replayed from the root with make/unmake
+struct PnNode {
+    parent: u32,
+    first_child: u32,
+    n_children: u8,
+    action: u8,
+    expanded: bool,
+    pn: u32,
+    dn: u32,
+}
+
+impl Board {
+    // proof-number search for a forced win of the side to move
+    pub fn proof_search(&self, limit: PnsLimit) -> ProofResult {
+        let attacker = self.player();
+        let mut b = self.clone();
+        let mut nodes = vec![PnNode {
+            parent: 0,
+            first_child: 0,
+            n_children: 0,
+            action: 0,
+            expanded: false,
+            pn: 1,
+            dn: 1,
+        }];
+        if b.result() != GameResult::Ongoing {
+            nodes[0].pn = PN_INF;
+            nodes[0].dn = 0;
+        }
+        let max_nodes = limit.max_nodes();
+        while nodes[0].pn != 0 && nodes[0].dn != 0 && nodes.len() + 16 <= max_nodes {
+            // descend to the most-proving node
+            let mut idx = 0;
+            while nodes[idx].expanded {
+                let or_node = b.player == attacker;
+                let first = nodes[idx].first_child as usize;
+                let mut best = first;
+                for child in first..first + nodes[idx].n_children as usize {
+                    let better = if or_node {
+                        nodes[child].pn < nodes[best].pn
+                    } else {
+                        nodes[child].dn < nodes[best].dn
+                    };
+                    if better {
+                        best = child;
+                    }
+                }
+                b.make(nodes[best].action);
+                idx = best;
+            }
+
+            pns_expand(&mut nodes, idx, &mut b, &attacker);
+
+            // back up the proof numbers to the root
+            loop {
+                let or_node = b.player == attacker;
+                let first = nodes[idx].first_child as usize;
+                let children = &nodes[first..first + nodes[idx].n_children as usize];
+                let (pn, dn) = if or_node {
+                    (
+                        children.iter().map(|c| c.pn).min().unwrap(),
+                        children.iter().fold(0u32, |a, c| a.saturating_add(c.dn)),
+                    )
+                } else {
+                    (
+                        children.iter().fold(0u32, |a, c| a.saturating_add(c.pn)),
+                        children.iter().map(|c| c.dn).min().unwrap(),
+                    )
+                };
+                nodes[idx].pn = pn;
+                nodes[idx].dn = dn;
+                if idx == 0 {
+                    break;
+                }
+                b.unmake();
+                idx = nodes[idx].parent as usize;
+            }
+        }
+
+        let status = if nodes[0].pn == 0 {
+            ProofStatus::Proven
+        } else if nodes[0].dn == 0 {
+            ProofStatus::Disproven
+        } else {
+            ProofStatus::Unknown
+        };
+        let tree = if status == ProofStatus::Proven && nodes[0].expanded {
+            pns_best_answer(&nodes, 0)
+        } else {
+            None
+        };
+        return ProofResult {
+            status: status,
+            distance: tree.as_ref().map(|t| t.depth),
+            action: tree.as_ref().map(|t| t.action),
+            tree: tree,
+            nodes: nodes.len(),
+        };
+    }
+}
+
+// the board is at nodes[idx]; attacker moves that are symmetric to an earlier sibling are
+// dropped, but every defender reply is kept so the proof tree answers all of them
+fn pns_expand(nodes: &mut Vec<PnNode>, idx: usize, b: &mut Board, attacker: &Player) {
+    let first = nodes.len();
+    let mover = b.player();
+    let mut seen = [0u64; 16];
+    let mut n_seen = 0;
+    for action in b.actions() {
+        b.make(action);
+        let key = b.canonical_key();
+        let (pn, dn) = if b.is_win() {
+            if mover == *attacker {
+                (0, PN_INF)
+            } else {
+                (PN_INF, 0)
+            }
+        } else if b.is_full() {
+            (PN_INF, 0)
+        } else {
+            (1, 1)
+        };
+        b.unmake();
+        if mover == *attacker && seen[..n_seen].contains(&key) {
+            continue;
+        }
+        seen[n_seen] = key;
+        n_seen += 1;
+        nodes.push(PnNode {
+            parent: idx as u32,
+            first_child: 0,
+            n_children: 0,
+            action: action,
+            expanded: false,
+            pn: pn,
+            dn: dn,
+        });
+    }
+    nodes[idx].first_child = first as u32;
+    nodes[idx].n_children = (nodes.len() - first) as u8;
+    nodes[idx].expanded = true;
+}
+
+// the quickest proven move of the attacker at nodes[idx]
+fn pns_best_answer(nodes: &Vec<PnNode>, idx: usize) -> Option<MateNode> {
+    let first = nodes[idx].first_child as usize;
+    return (first..first + nodes[idx].n_children as usize)
+        .filter(|c| nodes[*c].pn == 0)
+        .map(|c| pns_tree(nodes, c))
+        .min_by_key(|t| t.depth);
+}
+
+// the proof tree under a proven attacker move; unexpanded proven nodes are wins on the board
+fn pns_tree(nodes: &Vec<PnNode>, idx: usize) -> MateNode {
+    let node = &nodes[idx];
+    let mut replies = Vec::new();
+    if node.expanded {
+        let first = node.first_child as usize;
+        for reply in first..first + node.n_children as usize {
+            replies.push(MateReply {
+                action: nodes[reply].action,
+                answer: pns_best_answer(nodes, reply).unwrap(),
+            });
+        }
+    }
+    return MateNode {
+        action: node.action,
+        depth: replies
+            .iter()
+            .map(|r| r.answer.depth + 2)
+            .max()
+            .unwrap_or(1),
+        replies: replies,
+    };
+}
+
+// default node limit of the record's search tree, a little over 100 MB of nodes
+pub const RECORD_TREE_NODES: usize = 1 << 21;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "RecordRepr")]
 pub struct Record {
     moves: Vec<u8>,
-    mcts: Option<RefCell<Node>>,
+    #[serde(skip)]
+    mcts: Option<Arc<Tree>>,
+    #[serde(skip)]
+    mcts_nodes: usize,
     cursor: usize,
     initial: bool,
 }
 
+#[derive(serde::Deserialize)]
+struct RecordRepr {
+    moves: Vec<u8>,
+    cursor: usize,
+    initial: bool,
+}
+
+impl TryFrom<RecordRepr> for Record {
+    type Error = String;
+
+    fn try_from(repr: RecordRepr) -> Result<Self, Self::Error> {
+        let mut board = Board::new();
+        for action in repr.moves.iter() {
+            board = board.try_next(*action).map_err(|e| e.to_string())?;
+        }
+        if repr.cursor >= repr.moves.len().max(1) {
+            return Err(format!("cursor {} is past the last move", repr.cursor));
+        }
+        // the initial board is only shown with the cursor on the first move
+        if repr.initial && repr.cursor != 0 {
+            return Err(format!(
+                "cursor {} is set on the initial board",
+                repr.cursor
+            ));
+        }
+        let initial = repr.initial || repr.moves.len() == 0;
+        return Ok(Record {
+            moves: repr.moves,
+            mcts: None,
+            mcts_nodes: RECORD_TREE_NODES,
+            cursor: repr.cursor,
+            initial: initial,
+        });
+    }
+}
+
 impl Record {
     pub fn new() -> Self {
         return Record {
             moves: vec![],
             cursor: 0,
             mcts: None,
+            mcts_nodes: RECORD_TREE_NODES,
             initial: true,
         };
     }
@@ -434,7 +1408,8 @@ impl Record {
         return self.get_last_board();
     }
 
-    pub fn push(&mut self, action: u8) {
+    pub fn push(&mut self, action: u8) -> Result<(), MoveError> {
+        self.get_last_board().try_next(action)?;
         if self.cursor + 1 < self.moves.len() {
             for _ in 0..(self.moves.len() - 1 - self.cursor) {
                 let _ = self.moves.pop();
@@ -447,320 +1422,311 @@ impl Record {
         self.moves.push(action);
         self.cursor = self.moves.len() - 1;
         self.initial = false;
+        return Ok(());
     }
 
-    pub fn push_and_board(&mut self, action: u8) -> Board {
-        self.push(action);
-        return self.get_last_board();
+    pub fn push_and_board(&mut self, action: u8) -> Result<Board, MoveError> {
+        self.push(action)?;
+        return Ok(self.get_last_board());
     }
 
-    pub fn run_mcts_evaluate(&mut self, search_n: usize) -> Vec<Score> {
+    pub fn ply(&self) -> usize {
+        if self.initial || self.moves.len() == 0 {
+            return 0;
+        }
+        return self.cursor + 1;
+    }
+
+    pub fn game_state(&self) -> GameState {
+        let board = self.get_last_board();
+        let result = board.result();
+        let legal_moves = if result == GameResult::Ongoing {
+            board
+                .valid_actions()
+                .into_iter()
+                .map(|action| LegalMove {
+                    action: action,
+                    layer: board.landing_layer(action).unwrap(),
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        return GameState {
+            player: board.player(),
+            ply: self.ply(),
+            total_plies: self.moves.len(),
+            legal_moves: legal_moves,
+            result: result,
+            winning_cells: board
+                .winning_cells()
+                .into_iter()
+                .map(Cell::from_index)
+                .collect(),
+            threats: board.threat_cells(),
+            board: board,
+        };
+    }
+
+    // the node limit of the analysis tree; a tree built under another limit is dropped
+    pub fn set_mcts_nodes(&mut self, nodes: usize) {
+        if nodes != self.mcts_nodes {
+            self.mcts_nodes = nodes;
+            self.mcts = None;
+        }
+    }
+
+    // the analysis tree and its node for the current board; the tree is kept while the board
+    // is reachable from its root, so playing on and stepping back both reuse it. A tree too
+    // full to follow the moves is rebuilt at the current board
+    pub fn mcts_tree(&mut self) -> (Arc<Tree>, Cursor) {
         let current_board = self.get_last_board();
-        match &self.mcts {
-            None => {
-                let mut node = Node::new(current_board);
-                let result = node.search(50, search_n);
-                self.mcts = Some(RefCell::new(node));
-                return result;
-            }
-            Some(node) => {
-                if node.borrow().board == current_board {
-                    println!("current board is same");
-                    let result = node.borrow_mut().search(50, search_n);
-                    return result;
-                } else {
-                    let mut node = Node::new(current_board);
-                    let result = node.search(50, search_n);
-                    self.mcts = Some(RefCell::new(node));
-                    return result;
-                }
+        if let Some(tree) = &self.mcts {
+            if let Some(cursor) = tree.cursor(&current_board) {
+                return (tree.clone(), cursor);
             }
         }
+        let tree = Arc::new(Tree::with_capacity(current_board, self.mcts_nodes));
+        self.mcts = Some(tree.clone());
+        let cursor = tree.root();
+        return (tree, cursor);
+    }
+
+    pub fn run_mcts_evaluate(&mut self, search_n: usize) -> Vec<Score> {
+        let (tree, cursor) = self.mcts_tree();
+        let config = MctsConfig::default();
+        return tree.search_at(&cursor, &config, search_n, default_threads());
     }
 }
 
 pub type MuRecord = Mutex<Record>;
 
-#[tauri::command]
-pub fn board_action(action: u8, record: State<'_, MuRecord>) -> String {
-    return record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .push_and_board(action)
-        .to_board_string();
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct LegalMove {
+    pub action: u8,
+    pub layer: u8,
 }
 
-#[tauri::command]
-pub fn board_next(record: State<'_, MuRecord>) -> String {
-    return record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .next()
-        .to_board_string();
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GameState {
+    pub board: Board,
+    pub player: Player,
+    pub ply: usize,
+    pub total_plies: usize,
+    pub legal_moves: Vec<LegalMove>,
+    pub result: GameResult,
+    pub winning_cells: Vec<Cell>,
+    pub threats: Vec<ThreatCell>,
 }
 
 #[tauri::command]
-pub fn board_back(record: State<'_, MuRecord>) -> String {
-    return record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .back()
-        .to_board_string();
+pub fn board_action(action: u8, record: State<'_, MuRecord>) -> Result<GameState, String> {
+    let mut record = record.inner().lock().unwrap();
+    record.push(action).map_err(|e| e.to_string())?;
+    return Ok(record.game_state());
 }
 
 #[tauri::command]
-pub fn board_init(record: State<'_, MuRecord>) -> String {
-    return record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .initial_board()
-        .to_board_string();
+pub fn board_next(record: State<'_, MuRecord>) -> GameState {
+    let mut record = record.inner().lock().unwrap();
+    record.next();
+    return record.game_state();
 }
 
 #[tauri::command]
-pub fn board_last(record: State<'_, MuRecord>) -> String {
-    return record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .jump_last_board()
-        .to_board_string();
+pub fn board_back(record: State<'_, MuRecord>) -> GameState {
+    let mut record = record.inner().lock().unwrap();
+    record.back();
+    return record.game_state();
 }
 
 #[tauri::command]
-pub fn search_mate(record: State<'_, MuRecord>) -> MateRow {
-    println!("search_mate called");
-    return _search_mate(&record.inner().lock().unwrap().deref().get_last_board(), 5);
+pub fn board_init(record: State<'_, MuRecord>) -> GameState {
+    let mut record = record.inner().lock().unwrap();
+    record.initial_board();
+    return record.game_state();
 }
 
 #[tauri::command]
-pub fn command_run_mcts(search_n: usize, record: State<'_, MuRecord>) -> Vec<Score> {
-    println!("command_run_mcts called");
-    let mut scores = record
-        .inner()
-        .lock()
-        .unwrap()
-        .deref_mut()
-        .run_mcts_evaluate(search_n);
-    scores.sort_by(|a, b| b.na.partial_cmp(&a.na).unwrap());
-    return scores;
+pub fn board_last(record: State<'_, MuRecord>) -> GameState {
+    let mut record = record.inner().lock().unwrap();
+    record.jump_last_board();
+    return record.game_state();
 }
 
-pub struct Node {
-    board: Board,
-    n: f32,
-    w: f32,
-    children: HashMap<u8, RefCell<Node>>,
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MateSolver {
+    AlphaBeta,
+    ProofNumber,
+    ThreatSpace,
 }
 
-#[derive(serde::Serialize, PartialEq, PartialOrd)]
-pub struct Score {
-    pub action: u8,
-    pub score: f32,
-    pub q: f32,
-    pub na: f32,
-    pub n: f32,
-}
-
-impl fmt::Debug for Score {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "action: {:>2}, score: {:>5.2}%({:>7.0}/{:>7.0}), Q: {:>5.3}",
-            self.action,
-            self.score * 100.0,
-            self.na,
-            self.n,
-            self.q
-        );
-        Ok(())
-    }
-}
-
-impl Node {
-    pub fn new(board: Board) -> Self {
-        return Node {
-            board: board,
-            children: HashMap::new(),
-            n: 1f32,
-            w: 0f32,
-        };
-    }
-
-    pub fn search(&mut self, expand_n: usize, search_n: usize) -> Vec<Score> {
-        if self.children.len() == 0 {
-            self.expand();
-            for (action, node) in self.children.iter() {
-                println!(
-                    "[expand] action:{}, board:{:x}, player:{:#?}, player':{:#?}",
-                    action,
-                    node.borrow().board.black,
-                    node.borrow().board.player,
-                    self.board.player,
-                )
-            }
+#[tauri::command]
+pub fn search_mate(
+    budget_ms: Option<u64>,
+    solver: Option<MateSolver>,
+    node_limit: Option<usize>,
+    record: State<'_, MuRecord>,
+) -> MateRow {
+    println!("search_mate called");
+    let board = record.inner().lock().unwrap().deref().get_last_board();
+    match (solver.unwrap_or(MateSolver::AlphaBeta), budget_ms) {
+        (MateSolver::ProofNumber, _) => {
+            _search_mate_pns(&board, PnsLimit::Nodes(node_limit.unwrap_or(2_000_000)))
         }
-        for _ in 0..search_n {
-            self.evaluate(expand_n);
+        (MateSolver::ThreatSpace, _) => {
+            _search_mate_tss(&board, node_limit.unwrap_or(2_000_000) as u64)
         }
-
-        let mut scores = Vec::new();
-        for (action, node) in self.children.iter() {
-            scores.push(Score {
-                action: *action,
-                score: node.borrow().n / self.n,
-                q: node.borrow().w / node.borrow().n,
-                na: node.borrow().n,
-                n: self.n,
-            });
-            // println!("{}/{}", node.borrow().n, self.n);
-        }
-        return scores;
-    }
-
-    fn evaluate(&mut self, expand_n: usize) -> f32 {
-        if self.board.is_win() {
-            self.w += 1.0;
-            self.n += 1.0;
-            return 1.0;
-        } else if self.board.is_draw() {
-            self.n += 1.0;
-            return 0.0;
-        } else if self.children.len() == 0 {
-            let value = -playout(&self.board);
-            self.w += value;
-            self.n += 1.0;
-            if self.n == expand_n as f32 {
-                self.expand();
-            }
-            return value;
-        } else {
-            let next_node_action = {
-                let children = &self.children;
-                let mut best_action = 0;
-                // (best_action, best_node) = &children[&0];
-                let mut max_score = -2.0;
-                for (action, node) in children.iter() {
-                    let ucb = node.borrow().get_uct(self.n);
-                    if ucb > max_score {
-                        max_score = ucb;
-                        best_action = *action;
-                    }
-                }
-                best_action
-            };
-            let value = -self
-                .children
-                .get(&next_node_action)
-                .unwrap()
-                .borrow_mut()
-                .evaluate(expand_n);
-            self.w += value;
-            self.n += 1.0;
-            return value;
-        }
-    }
-
-    fn expand(&mut self) {
-        let mut nodes = HashMap::new();
-        let mut set: HashSet<u128> = HashSet::new();
-        for action in self.board.valid_actions() {
-            let next_board = self.board.next(action);
-            assert_eq!(next_board.player, next_board.clone().player);
-            // println!("{:#?}, {:#?}", self.board.player, next_board.clone().player);
-            if !set.contains(&next_board.hash()) {
-                nodes.insert(action, RefCell::new(Node::new(next_board.clone())));
-                set.insert(next_board.hash());
-            }
+        (MateSolver::AlphaBeta, Some(ms)) => {
+            _search_mate_timed(&board, Budget::Time(Duration::from_millis(ms)))
         }
-        self.children = nodes
+        (MateSolver::AlphaBeta, None) => _search_mate(&board, 5),
     }
+}
 
-    fn get_uct(&self, N: f32) -> f32 {
-        return self.w / self.n + (2.0 * N.ln() / self.n).sqrt();
-    }
+#[tauri::command]
+pub fn analyze_danger(node_limit: Option<usize>, record: State<'_, MuRecord>) -> DangerReport {
+    println!("analyze_danger called");
+    let board = record.inner().lock().unwrap().deref().get_last_board();
+    return _analyze_danger(&board, PnsLimit::Nodes(node_limit.unwrap_or(200_000)));
 }
 
-pub fn mcts_action(board: &Board, n: usize, ex_n: usize) -> u8 {
-    let mut node = Node::new(board.clone());
-    let scores = node.search(ex_n, n);
-    // let mut max_action = 0;
-    let mut max_actions = Vec::new();
-    let mut max_score = -2.0;
-    for score in scores {
-        if score.score > max_score {
-            max_score = score.score;
-            // max_action = score.action;
-            max_actions = vec![score.action];
-        } else if score.score == max_score {
-            max_actions.push(score.action);
-        }
-    }
-    let mut rng = rand::thread_rng();
-    return max_actions[rng.gen::<usize>() % max_actions.len()];
-    // return max_action;
+// async so the search runs off the main thread; the record is only locked to find the tree,
+// so the board stays usable while it runs. A borrowed State needs a Result in async commands
+#[tauri::command(async)]
+pub fn command_run_mcts(
+    search_n: usize,
+    threads: Option<usize>,
+    config: Option<MctsConfig>,
+    node_limit: Option<usize>,
+    record: State<'_, MuRecord>,
+) -> Result<Vec<Score>, String> {
+    println!("command_run_mcts called");
+    let (tree, cursor) = {
+        let mut record = record.inner().lock().unwrap();
+        record.set_mcts_nodes(node_limit.unwrap_or(RECORD_TREE_NODES));
+        record.mcts_tree()
+    };
+    let mut scores = tree.search_at(
+        &cursor,
+        &config.unwrap_or_default(),
+        search_n,
+        threads.unwrap_or_else(default_threads),
+    );
+    // proven wins first and proven losses last, whatever their visits
+    let rank = |s: &Score| match s.proof {
+        Proof::Win => 0,
+        Proof::Loss => 2,
+        _ => 1,
+    };
+    scores.sort_by(|a, b| rank(a).cmp(&rank(b)).then(b.na.partial_cmp(&a.na).unwrap()));
+    return Ok(scores);
+}
+
+pub fn mcts_action(board: &Board, config: &MctsConfig, n: usize) -> u8 {
+    let tree = Tree::new(board.clone());
+    let scores = tree.search(config, n, default_threads());
+    return config.final_action(&scores).unwrap();
+}
+
+pub fn mcts_graph_action(board: &Board, memory: usize, n: usize) -> u8 {
+    let scores = Graph::new(memory).search(board, n);
+    return MctsConfig::default().final_action(&scores).unwrap();
 }
 
 pub enum Agent {
     Human,
     Random,
     Minimax(u8),
-    Mcts(usize, usize),
+    Iterative(Budget),
+    // config, playouts
+    Mcts(MctsConfig, usize),
+    // table bytes, playouts
+    MctsGraph(usize, usize),
 }
 
 impl Agent {
     pub fn get_action(&self, board: &Board) -> u8 {
         match self {
-            Agent::Human => {
+            Agent::Human => loop {
                 input! {
                     action: u8
                 }
-                action
-            }
+                match board.try_next(action) {
+                    Ok(_) => break action,
+                    Err(e) => println!("invalid move: {}", e),
+                }
+            },
             Agent::Minimax(depth) => board.minimax_action(*depth),
-            Agent::Mcts(expand_n, search_n) => mcts_action(board, *search_n, *expand_n),
+            Agent::Iterative(budget) => {
+                Searcher::for_budget(*budget)
+                    .iterative(board, *budget)
+                    .action
+            }
+            Agent::Mcts(config, search_n) => mcts_action(board, config, *search_n),
+            Agent::MctsGraph(memory, search_n) => mcts_graph_action(board, *memory, *search_n),
             Agent::Random => get_random(board),
         }
     }
 
+    // get_action for a player that moves again later in the same game; Agent::Mcts keeps its
+    // tree in `tree` and picks up from the node of the new board
+    pub fn get_action_with(&self, board: &Board, tree: &mut Option<Tree>) -> u8 {
+        match self {
+            Agent::Mcts(config, search_n) => {
+                let cursor = match tree.as_ref().and_then(|t| t.cursor(board)) {
+                    Some(cursor) => cursor,
+                    None => tree.insert(Tree::new(board.clone())).root(),
+                };
+                let scores =
+                    tree.as_ref()
+                        .unwrap()
+                        .search_at(&cursor, config, *search_n, default_threads());
+                return config.final_action(&scores).unwrap();
+            }
+            _ => self.get_action(board),
+        }
+    }
+
     pub fn name(&self) -> String {
         match self {
             Agent::Human => String::from("Human"),
             Agent::Random => String::from("Random"),
             Agent::Minimax(depth) => format!("Minimax:{}", depth),
-            Agent::Mcts(ex, se) => format!("Mcts:{}/{}", se, ex),
+            Agent::Iterative(budget) => format!("Iterative:{}", budget),
+            Agent::Mcts(config, se) => format!("Mcts:{}/{}", se, config),
+            Agent::MctsGraph(memory, se) => format!("MctsGraph:{}/{}MB", se, memory >> 20),
         }
     }
 }
 
+// an agent that returns an illegal move forfeits the game
 pub fn play(a1: &Agent, a2: &Agent) -> (f32, f32) {
+    match play_result(a1, a2) {
+        GameResult::Won(Player::Black) => (1.0, 0.0),
+        GameResult::Won(Player::White) => (0.0, 1.0),
+        _ => (0.5, 0.5),
+    }
+}
+
+// a1 plays Black, a2 plays White
+pub fn play_result(a1: &Agent, a2: &Agent) -> GameResult {
     let mut b = Board::new();
+    let mut trees: [Option<Tree>; 2] = [None, None];
     loop {
-        if b.is_black() {
-            let action = a1.get_action(&b);
-            b = b.next(action);
-            if b.is_win() {
-                return (1.0, 0.0);
-            } else if b.is_draw() {
-                return (0.5, 0.5);
-            }
-        } else {
-            let action = a2.get_action(&b);
-            b = b.next(action);
-            if b.is_win() {
-                return (0.0, 1.0);
-            } else if b.is_draw() {
-                return (0.5, 0.5);
-            }
+        let player = b.player();
+        let agent = match player {
+            Player::Black => a1,
+            Player::White => a2,
+        };
+        let action = agent.get_action_with(&b, &mut trees[player.clone() as usize]);
+        b = match b.try_next(action) {
+            Ok(next_board) => next_board,
+            Err(_) => return GameResult::Won(player.next()),
+        };
+        let result = b.result();
+        if result != GameResult::Ongoing {
+            return result;
         }
     }
 }
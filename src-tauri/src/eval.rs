@@ -0,0 +1,107 @@
+use super::board::{Board, Player, LINES};
+use super::search::MATE_BOUND;
+use std::fs;
+use std::sync::OnceLock;
+
+// set to a JSON file of EvalWeights to tune the evaluation without rebuilding
+pub const WEIGHTS_ENV: &str = "QUBIC_EVAL_WEIGHTS";
+
+// weights of the static evaluation, in the same units as search scores
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct EvalWeights {
+    // lines with k own stones and no opposing stone, index k - 1
+    pub open: [i32; 3],
+    // a winning cell that can be taken by the next stone in its column
+    pub playable_threat: i32,
+    // a winning cell that still needs support underneath
+    pub pending_threat: i32,
+    // a pending threat on a layer the owner fills by parity: even layers for Black, odd for White
+    pub parity_threat: i32,
+    // taken off a pending threat for every empty cell under it
+    pub threat_height: i32,
+    // bonus for the side to move
+    pub tempo: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        return EvalWeights {
+            open: [1, 4, 16],
+            playable_threat: 64,
+            pending_threat: 24,
+            parity_threat: 24,
+            threat_height: 4,
+            tempo: 2,
+        };
+    }
+}
+
+impl EvalWeights {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        return serde_json::from_str(json).map_err(|e| e.to_string());
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        return EvalWeights::from_json(&json);
+    }
+
+    // the weights named by QUBIC_EVAL_WEIGHTS, or the defaults; read once per process
+    pub fn global() -> &'static EvalWeights {
+        static WEIGHTS: OnceLock<EvalWeights> = OnceLock::new();
+        return WEIGHTS.get_or_init(|| match std::env::var(WEIGHTS_ENV) {
+            Ok(path) => EvalWeights::from_file(&path).unwrap_or_else(|e| {
+                println!("could not load eval weights, using defaults: {}", e);
+                EvalWeights::default()
+            }),
+            Err(_) => EvalWeights::default(),
+        });
+    }
+
+    // score for the side to move, kept inside the non-mate range
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        let player = board.player();
+        let score = self.player_score(board, &player) - self.player_score(board, &player.next())
+            + self.tempo;
+        return score.clamp(-MATE_BOUND + 1, MATE_BOUND - 1);
+    }
+
+    // one pass over the lines; this runs at every leaf so it avoids LineCounts and threats()
+    fn player_score(&self, board: &Board, player: &Player) -> i32 {
+        let own = board.stones(player);
+        let other = board.stones(&player.next());
+        let mut score = 0;
+        let mut threats = 0u64;
+        for line in LINES.iter() {
+            let k = (own & line).count_ones() as usize;
+            if other & line != 0 || k == 0 || k == 4 {
+                continue;
+            }
+            score += self.open[k - 1];
+            if k == 3 {
+                threats |= line & !own;
+            }
+        }
+        let playable = board.playable_cells();
+        score += self.playable_threat * (threats & playable).count_ones() as i32;
+        let occupied = board.black | board.white;
+        let good_parity = match player {
+            Player::Black => 0,
+            Player::White => 1,
+        };
+        let mut pending = threats & !playable;
+        while pending != 0 {
+            let cell = pending.trailing_zeros() as u8;
+            pending &= pending - 1;
+            let layer = cell / 16;
+            let column = 0x0001000100010001u64 << (cell % 16);
+            let support = layer - (occupied & column).count_ones() as u8;
+            score += self.pending_threat - self.threat_height * support as i32;
+            if layer % 2 == good_parity {
+                score += self.parity_threat;
+            }
+        }
+        return score;
+    }
+}
@@ -0,0 +1,111 @@
+use super::board::{mask_to_cells, Board, Cell, Player, LINES};
+
+// stones each player has on every one of the 76 lines, indexed like LINES
+#[derive(Clone, Debug)]
+pub struct LineCounts {
+    pub black: [u8; 76],
+    pub white: [u8; 76],
+}
+
+impl LineCounts {
+    pub fn new(board: &Board) -> Self {
+        let mut counts = LineCounts {
+            black: [0; 76],
+            white: [0; 76],
+        };
+        for i in 0..76 {
+            counts.black[i] = (board.black & LINES[i]).count_ones() as u8;
+            counts.white[i] = (board.white & LINES[i]).count_ones() as u8;
+        }
+        return counts;
+    }
+
+    pub fn own(&self, player: &Player) -> &[u8; 76] {
+        match player {
+            Player::Black => &self.black,
+            Player::White => &self.white,
+        }
+    }
+
+    // indices of the lines holding exactly k stones of player and none of the opponent
+    pub fn open_lines(&self, player: &Player, k: u8) -> impl Iterator<Item = usize> + '_ {
+        let own = self.own(player);
+        let other = self.own(&player.next());
+        return (0..76).filter(move |i| own[*i] == k && other[*i] == 0);
+    }
+
+    pub fn count_open(&self, player: &Player, k: u8) -> usize {
+        return self.open_lines(player, k).count();
+    }
+}
+
+// empty cells that would complete a line for a player
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Threats {
+    // can be taken by the next stone dropped in its column
+    pub playable: u64,
+    // still needs stones underneath before it can be taken
+    pub pending: u64,
+}
+
+impl Threats {
+    pub fn all(&self) -> u64 {
+        return self.playable | self.pending;
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ThreatCell {
+    pub cell: Cell,
+    pub player: Player,
+    pub playable: bool,
+}
+
+impl Board {
+    // cells a stone would land on, one per non-full column
+    pub fn playable_cells(&self) -> u64 {
+        let board = self.black | self.white;
+        return !board & ((board << 16) | 0xffff);
+    }
+
+    pub fn line_counts(&self) -> LineCounts {
+        return LineCounts::new(self);
+    }
+
+    pub fn open_lines(&self, player: &Player, k: u8) -> Vec<u64> {
+        let counts = self.line_counts();
+        return counts.open_lines(player, k).map(|i| LINES[i]).collect();
+    }
+
+    pub fn threats(&self, player: &Player) -> Threats {
+        let own = self.stones(player);
+        let empty = !(self.black | self.white);
+        let mut cells = 0u64;
+        for line in LINES.iter() {
+            let rest = line & !own;
+            if rest.count_ones() == 1 && rest & empty != 0 {
+                cells |= rest;
+            }
+        }
+        let playable = self.playable_cells();
+        return Threats {
+            playable: cells & playable,
+            pending: cells & !playable,
+        };
+    }
+
+    pub fn threat_cells(&self) -> Vec<ThreatCell> {
+        let mut cells = Vec::new();
+        for player in [Player::Black, Player::White] {
+            let threats = self.threats(&player);
+            for idx in mask_to_cells(threats.all()) {
+                cells.push(ThreatCell {
+                    cell: Cell::from_index(idx),
+                    player: player.clone(),
+                    playable: (threats.playable >> idx) & 1 == 1,
+                });
+            }
+        }
+        return cells;
+    }
+}
@@ -1,11 +1,18 @@
 mod board;
+mod eval;
 mod exp;
+mod lines;
+mod mcts;
+mod mcts_graph;
+mod search;
 mod tests;
+mod tss;
 
 use board::{
-    board_action, board_back, board_init, board_last, board_next, command_run_mcts, pprint_board,
-    search_mate, Agent, MuRecord, Record,
+    analyze_danger, board_action, board_back, board_init, board_last, board_next, command_run_mcts,
+    pprint_board, search_mate, Agent, MuRecord, Record,
 };
+use mcts::{MctsConfig, Rave, Selection};
 use proconio::input;
 use std::sync::Mutex;
 use tauri::{Manager, State};
@@ -43,6 +50,7 @@ fn main() {
             board_init,
             board_last,
             search_mate,
+            analyze_danger,
             command_run_mcts
         ])
         .run(tauri::generate_context!())
@@ -66,29 +74,26 @@ fn main() {
 // }
 
 fn rate_analysis() {
-    let agents = vec![
-        Agent::Random,
-        Agent::Mcts(50, 100),
-        Agent::Mcts(50, 200),
-        Agent::Mcts(50, 300),
-        Agent::Mcts(50, 400),
-        Agent::Mcts(50, 500),
-        Agent::Mcts(50, 600),
-        Agent::Mcts(50, 700),
-        Agent::Mcts(50, 800),
-        Agent::Mcts(50, 900),
-        Agent::Mcts(50, 1000),
-        Agent::Mcts(50, 1100),
-        Agent::Mcts(50, 1200),
-        Agent::Mcts(50, 1300),
-        Agent::Mcts(50, 1400),
-        Agent::Mcts(50, 1500),
-        Agent::Mcts(50, 1600),
-        Agent::Mcts(50, 1700),
-        Agent::Mcts(50, 1800),
-        Agent::Mcts(50, 1900),
-        Agent::Mcts(50, 2000),
-    ];
+    let mut agents = vec![Agent::Random];
+    for search_n in (100..=2000).step_by(100) {
+        agents.push(Agent::Mcts(MctsConfig::default(), search_n));
+    }
+    // the selection formulas against each other at the same number of playouts
+    for selection in [Selection::Ucb1Tuned, Selection::Puct] {
+        let config = MctsConfig {
+            selection: selection,
+            ..MctsConfig::default()
+        };
+        agents.push(Agent::Mcts(config, 1000));
+    }
+    // RAVE against the plain Mcts:1000 above
+    for rave in [Rave::Equivalence(1000.0), Rave::MinMse(0.1)] {
+        let config = MctsConfig {
+            rave: rave,
+            ..MctsConfig::default()
+        };
+        agents.push(Agent::Mcts(config, 1000));
+    }
 
     let rates = vec![
         1500.0, 1701.7267, 2146.0044, 2262.773, 2335.1033, 1889.678, 2110.4883, 2147.6833,
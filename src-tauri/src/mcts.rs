@@ -0,0 +1,772 @@
+use super::board::{planar_action, playout, Board, GameResult};
+use super::eval::EvalWeights;
+use super::search::MATE_BOUND;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+
+// set to the number of search threads; every core is used by default
+pub const THREADS_ENV: &str = "QUBIC_MCTS_THREADS";
+
+// charged to a node on the way down and refunded on the way up, so threads that select
+// while a playout is still running spread over other moves
+const VIRTUAL_LOSS: f64 = 1.0;
+
+// fewer playouts than this per thread are not worth starting a thread for
+const MIN_PLAYOUTS_PER_THREAD: usize = 256;
+
+pub fn default_threads() -> usize {
+    static THREADS: OnceLock<usize> = OnceLock::new();
+    return *THREADS.get_or_init(|| match std::env::var(THREADS_ENV) {
+        Ok(threads) => threads.parse().unwrap_or_else(|e| {
+            println!("could not read {}, using every core: {}", THREADS_ENV, e);
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }),
+        Err(_) => thread::available_parallelism().map_or(1, |n| n.get()),
+    });
+}
+
+// eval points per unit of prior logit; lower makes PUCT trust the evaluation more
+const PRIOR_TEMPERATURE: f64 = 32.0;
+
+// how a node picks the child to descend into
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Selection {
+    // Q + c * sqrt(ln N / n)
+    Ucb1,
+    // UCB1 with the exploration term scaled by the child's value variance (Auer et al.)
+    Ucb1Tuned,
+    // Q + c * P * sqrt(N) / n, with priors P from the static evaluation of each move
+    Puct,
+}
+
+// how much of a child's value comes from its all-moves-as-first statistics: every later move
+// of the same player on the child's cell counts for it, which suits Qubic, where a cell is
+// worth much the same whenever it is taken. β goes from 1 towards 0 as the child's own visits
+// n grow
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Rave {
+    Off,
+    // β = sqrt(k / (3n + k)), an even blend at n = k visits (Gelly and Silver)
+    Equivalence(f64),
+    // β = ñ / (n + ñ + 4 n ñ b²) for ñ AMAF visits and an AMAF bias b (Silver)
+    MinMse(f64),
+}
+
+impl Rave {
+    fn beta(&self, n: f64, amaf_n: f64) -> f64 {
+        return match self {
+            Rave::Off => 0.0,
+            Rave::Equivalence(k) => (k / (3.0 * n + k)).sqrt(),
+            Rave::MinMse(bias) => amaf_n / (n + amaf_n + 4.0 * n * amaf_n * bias * bias),
+        };
+    }
+}
+
+// which root move is played once the search is over; proven wins and losses come first
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FinalMove {
+    MostVisits,
+    BestValue,
+    // the best lower bound Q - 1 / sqrt(n), a good value backed by enough visits
+    Secure,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MctsConfig {
+    pub selection: Selection,
+    // multiplies the exploration term of the selection formula
+    pub exploration: f64,
+    // a leaf is expanded once it has this many visits
+    pub expand_n: u32,
+    // value assumed for a child with no playouts yet; 0 treats it as a draw
+    pub fpu: f64,
+    pub final_move: FinalMove,
+    pub rave: Rave,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        return MctsConfig {
+            selection: Selection::Ucb1,
+            exploration: std::f64::consts::SQRT_2,
+            expand_n: 50,
+            fpu: 0.0,
+            final_move: FinalMove::MostVisits,
+            rave: Rave::Off,
+        };
+    }
+}
+
+impl fmt::Display for MctsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}({:.2})/{}",
+            self.selection, self.exploration, self.expand_n
+        )?;
+        if self.fpu != 0.0 {
+            write!(f, "/fpu{}", self.fpu)?;
+        }
+        if self.final_move != FinalMove::MostVisits {
+            write!(f, "/{:?}", self.final_move)?;
+        }
+        if self.rave != Rave::Off {
+            write!(f, "/{:?}", self.rave)?;
+        }
+        Ok(())
+    }
+}
+
+impl MctsConfig {
+    // the move to play from the scores of a finished search, by final_move; proven losses
+    // are only played when nothing else is left, and the value rules pass over moves without
+    // playouts, whose Q is only the pseudo-visit's 0
+    pub fn final_action(&self, scores: &[Score]) -> Option<u8> {
+        if let Some(win) = scores.iter().find(|s| s.proof == Proof::Win) {
+            return Some(win.action);
+        }
+        let lost = scores.iter().all(|s| s.proof == Proof::Loss);
+        let key = |s: &Score| match self.final_move {
+            FinalMove::MostVisits => s.na,
+            _ if s.na < 1.0 => f32::MIN,
+            FinalMove::BestValue => s.q,
+            FinalMove::Secure => s.q - 1.0 / s.na.max(1.0).sqrt(),
+        };
+        let mut max_actions = Vec::new();
+        let mut max_key = f32::MIN;
+        for score in scores {
+            if score.proof == Proof::Loss && !lost {
+                continue;
+            }
+            let k = key(score);
+            if k > max_key {
+                max_key = k;
+                max_actions = vec![score.action];
+            } else if k == max_key {
+                max_actions.push(score.action);
+            }
+        }
+        if max_actions.is_empty() {
+            return None;
+        }
+        return Some(max_actions[rand::random::<usize>() % max_actions.len()]);
+    }
+}
+
+// the first chunk of the arena; each further chunk doubles the capacity
+const FIRST_CHUNK: usize = 1 << 12;
+const CHUNKS: usize = 20;
+// nodes in every chunk together, just under u32::MAX
+const CAPACITY: usize = FIRST_CHUNK * ((1 << CHUNKS) - 1);
+
+const ROOT: u32 = 0;
+
+// expansion states of a node
+const LEAF: u8 = 0;
+const EXPANDING: u8 = 1;
+const EXPANDED: u8 = 2;
+
+// a value the search has proven, from the view of the player who moved into the node
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Debug)]
+pub enum Proof {
+    Unknown,
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Proof {
+    fn from_u8(proof: u8) -> Self {
+        match proof {
+            1 => Proof::Win,
+            2 => Proof::Loss,
+            3 => Proof::Draw,
+            _ => Proof::Unknown,
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        match self {
+            Proof::Unknown => None,
+            Proof::Win => Some(1.0),
+            Proof::Loss => Some(-1.0),
+            Proof::Draw => Some(0.0),
+        }
+    }
+}
+
+// a node does not store its board; it is replayed from the root on the way down.
+// Every field is atomic so that any number of threads can search one tree; the fields set by
+// the expansion are published by the Release store of `state`
+#[derive(Default)]
+struct Node {
+    n: AtomicU32,
+    // sum of playout values from the view of the player who moved into this node, as f64 bits
+    w: AtomicU64,
+    // sum of their squares, for UCB1-Tuned
+    w2: AtomicU64,
+    // PUCT prior of the move into this node, as f32 bits
+    prior: AtomicU32,
+    // AMAF visits and value sum of the move into this node, as for n and w
+    amaf_n: AtomicU32,
+    amaf_w: AtomicU64,
+    first_child: AtomicU32,
+    action: AtomicU8,
+    // the cell the move into this node fills
+    cell: AtomicU8,
+    n_children: AtomicU8,
+    state: AtomicU8,
+    proof: AtomicU8,
+}
+
+impl Node {
+    fn init(&self, action: u8, cell: u8, prior: f32) {
+        self.action.store(action, Ordering::Relaxed);
+        self.cell.store(cell, Ordering::Relaxed);
+        self.n.store(1, Ordering::Relaxed);
+        self.w.store(0f64.to_bits(), Ordering::Relaxed);
+        self.w2.store(0f64.to_bits(), Ordering::Relaxed);
+        self.prior.store(prior.to_bits(), Ordering::Relaxed);
+        self.amaf_n.store(0, Ordering::Relaxed);
+        self.amaf_w.store(0f64.to_bits(), Ordering::Relaxed);
+    }
+
+    fn w(&self) -> f64 {
+        return f64::from_bits(self.w.load(Ordering::Relaxed));
+    }
+
+    fn add_w(&self, value: f64) {
+        add_f64(&self.w, value);
+    }
+
+    fn proof(&self) -> Proof {
+        return Proof::from_u8(self.proof.load(Ordering::Relaxed));
+    }
+
+    fn set_proof(&self, proof: Proof) {
+        self.proof.store(proof as u8, Ordering::Relaxed);
+    }
+
+    fn children(&self) -> Option<std::ops::Range<u32>> {
+        if self.state.load(Ordering::Acquire) != EXPANDED {
+            return None;
+        }
+        let first = self.first_child.load(Ordering::Relaxed);
+        return Some(first..first + self.n_children.load(Ordering::Relaxed) as u32);
+    }
+
+    // n counts the pseudo-visit of init, so a child without playouts has n == 1
+    fn get_score(&self, parent_n: f64, config: &MctsConfig) -> f64 {
+        let n = self.n.load(Ordering::Relaxed) as f64;
+        let q = if n <= 1.0 { config.fpu } else { self.w() / n };
+        let amaf_n = self.amaf_n.load(Ordering::Relaxed) as f64;
+        let q = if amaf_n > 0.0 {
+            let amaf_q = f64::from_bits(self.amaf_w.load(Ordering::Relaxed)) / amaf_n;
+            let beta = config.rave.beta(n, amaf_n);
+            (1.0 - beta) * q + beta * amaf_q
+        } else {
+            q
+        };
+        let c = config.exploration;
+        return match config.selection {
+            Selection::Ucb1 => q + c * (parent_n.ln() / n).sqrt(),
+            Selection::Ucb1Tuned => {
+                // the [0, 1] bound of 1/4 on the variance becomes 1 for values in [-1, 1]
+                let w2 = f64::from_bits(self.w2.load(Ordering::Relaxed));
+                let mean = self.w() / n;
+                let variance = (w2 / n - mean * mean).max(0.0);
+                let bound = variance + 4.0 * (2.0 * parent_n.ln() / n).sqrt();
+                q + c * (parent_n.ln() / n * bound.min(1.0)).sqrt()
+            }
+            Selection::Puct => {
+                let prior = f32::from_bits(self.prior.load(Ordering::Relaxed)) as f64;
+                q + c * prior * parent_n.sqrt() / n
+            }
+        };
+    }
+}
+
+fn add_f64(atomic: &AtomicU64, value: f64) {
+    let _ = atomic.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |w| {
+        Some((f64::from_bits(w) + value).to_bits())
+    });
+}
+
+// nodes live in chunks that never move once allocated, so growing the arena does not copy
+// or block the nodes that other threads are reading
+struct Arena {
+    chunks: [OnceLock<Box<[Node]>>; CHUNKS],
+    len: AtomicUsize,
+    capacity: usize,
+}
+
+impl Arena {
+    fn new(capacity: usize) -> Self {
+        return Arena {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            len: AtomicUsize::new(0),
+            capacity: capacity.min(CAPACITY),
+        };
+    }
+
+    // the first index of `count` new nodes, None when they do not fit
+    fn alloc(&self, count: usize) -> Option<u32> {
+        let first = self
+            .len
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |len| {
+                (len + count <= self.capacity).then(|| len + count)
+            })
+            .ok()?;
+        return Some(first as u32);
+    }
+
+    fn get(&self, idx: u32) -> &Node {
+        let chunk = (idx as usize / FIRST_CHUNK + 1).ilog2() as usize;
+        let offset = idx as usize - FIRST_CHUNK * ((1 << chunk) - 1);
+        let nodes = self.chunks[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| Node::default()).collect());
+        return &nodes[offset];
+    }
+
+    fn len(&self) -> usize {
+        return self.len.load(Ordering::Relaxed);
+    }
+}
+
+// node 0 holds `board`; searches can start from any node below it
+pub struct Tree {
+    board: Board,
+    arena: Arena,
+}
+
+// a node of a tree together with its board. Symmetric moves share a child, so the node's
+// own position can be a planar image of the board that was asked for
+#[derive(Clone)]
+pub struct Cursor {
+    node: u32,
+    // the nodes from the root of the tree down to the node's parent
+    ancestors: Vec<u32>,
+    board: Board,
+    // the position of the node, and the symmetry that maps `board` onto it
+    frame: Board,
+    sym: usize,
+}
+
+impl Cursor {
+    pub fn board(&self) -> &Board {
+        return &self.board;
+    }
+
+    // an action of the node's position as an action of `board`
+    fn board_action(&self, action: u8) -> u8 {
+        return (0..16)
+            .find(|a| planar_action(self.sym, *a) == action)
+            .unwrap();
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, PartialOrd)]
+pub struct Score {
+    pub action: u8,
+    pub score: f32,
+    pub q: f32,
+    pub na: f32,
+    pub n: f32,
+    // from the view of the player making this move
+    pub proof: Proof,
+}
+
+impl fmt::Debug for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "action: {:>2}, score: {:>5.2}%({:>7.0}/{:>7.0}), Q: {:>5.3}, {:?}",
+            self.action,
+            self.score * 100.0,
+            self.na,
+            self.n,
+            self.q,
+            self.proof
+        );
+        Ok(())
+    }
+}
+
+impl Tree {
+    pub fn new(board: Board) -> Self {
+        return Tree::with_capacity(board, CAPACITY);
+    }
+
+    // a tree of at most `nodes` nodes; once it is full, searches go on without expanding
+    pub fn with_capacity(board: Board, nodes: usize) -> Self {
+        let arena = Arena::new(nodes.max(1));
+        let root = arena.alloc(1).unwrap();
+        arena.get(root).init(0, 0, 1.0);
+        return Tree {
+            board: board,
+            arena: arena,
+        };
+    }
+
+    pub fn board(&self) -> &Board {
+        return &self.board;
+    }
+
+    // nodes in the arena
+    pub fn len(&self) -> usize {
+        return self.arena.len();
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.arena.capacity;
+    }
+
+    pub fn root(&self) -> Cursor {
+        return Cursor {
+            node: ROOT,
+            ancestors: Vec::new(),
+            board: self.board.clone(),
+            frame: self.board.clone(),
+            sym: 0,
+        };
+    }
+
+    // the node for a board whose history continues this tree's board, expanding the nodes on
+    // the way; a move without a child of its own follows the sibling it is symmetric to.
+    // None when the board is not reachable from here
+    pub fn cursor(&self, board: &Board) -> Option<Cursor> {
+        let base = self.board.moves();
+        let moves = board.moves();
+        if moves.len() < base.len() || moves[..base.len()] != base[..] {
+            return None;
+        }
+        let mut cursor = self.root();
+        for &action in moves[base.len()..].iter() {
+            if cursor.frame.result() != GameResult::Ongoing {
+                return None;
+            }
+            let children = loop {
+                let node = self.arena.get(cursor.node);
+                match node.children() {
+                    Some(children) => break children,
+                    None if node.state.load(Ordering::Relaxed) == LEAF => {
+                        if !self.expand(cursor.node, &cursor.frame, None) {
+                            return None;
+                        }
+                    }
+                    // another thread is expanding it right now
+                    None => thread::yield_now(),
+                }
+            };
+            let target = cursor
+                .frame
+                .next(planar_action(cursor.sym, action))
+                .canonical_key();
+            cursor.ancestors.push(cursor.node);
+            cursor.node = children.into_iter().find(|idx| {
+                let child = self.arena.get(*idx).action.load(Ordering::Relaxed);
+                cursor.frame.next(child).canonical_key() == target
+            })?;
+            cursor
+                .frame
+                .make(self.arena.get(cursor.node).action.load(Ordering::Relaxed));
+            cursor.board.make(action);
+            cursor.sym = cursor.board.symmetry_to(&cursor.frame)?;
+        }
+        return Some(cursor);
+    }
+
+    pub fn search(&self, config: &MctsConfig, search_n: usize, threads: usize) -> Vec<Score> {
+        return self.search_at(&self.root(), config, search_n, threads);
+    }
+
+    // playouts below the cursor are backed up to the root of the tree as well, so the tree
+    // stays consistent when a search moves back up. A tree can be searched with different
+    // configs in turn; nodes expanded without PUCT keep uniform priors
+    pub fn search_at(
+        &self,
+        cursor: &Cursor,
+        config: &MctsConfig,
+        search_n: usize,
+        threads: usize,
+    ) -> Vec<Score> {
+        if cursor.frame.result() == GameResult::Ongoing {
+            self.expand(cursor.node, &cursor.frame, Some(config));
+        }
+        let threads = threads
+            .min(search_n.div_ceil(MIN_PLAYOUTS_PER_THREAD))
+            .max(1);
+        let remaining = AtomicUsize::new(search_n);
+        let root = self.arena.get(cursor.node);
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| {
+                    let mut path = Vec::new();
+                    let mut cells = Vec::new();
+                    // nothing is left to learn once the root is solved
+                    while root.proof() == Proof::Unknown
+                        && remaining
+                            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+                                r.checked_sub(1)
+                            })
+                            .is_ok()
+                    {
+                        self.simulate(cursor, config, &mut path, &mut cells);
+                    }
+                });
+            }
+        });
+
+        // every node starts at one visit of its own; the scores count playouts only, so the
+        // visits of the moves never add up to more than those of the node
+        let n = (root.n.load(Ordering::Relaxed) - 1) as f32;
+        let mut scores = Vec::new();
+        for idx in root.children().unwrap_or(0..0) {
+            let node = self.arena.get(idx);
+            let visits = node.n.load(Ordering::Relaxed);
+            let na = (visits - 1) as f32;
+            scores.push(Score {
+                action: cursor.board_action(node.action.load(Ordering::Relaxed)),
+                score: na / n.max(1.0),
+                q: (node.w() / visits as f64) as f32,
+                na: na,
+                n: n,
+                proof: node.proof(),
+            });
+        }
+        return scores;
+    }
+
+    // one selection, playout and backup; every node on the path is charged a virtual loss
+    // until the value comes back
+    fn simulate(
+        &self,
+        cursor: &Cursor,
+        config: &MctsConfig,
+        path: &mut Vec<u32>,
+        cells: &mut Vec<u8>,
+    ) {
+        let mut b = cursor.frame.clone();
+        let mut idx = cursor.node;
+        path.clear();
+        cells.clear();
+        path.push(idx);
+        self.arena.get(idx).n.fetch_add(1, Ordering::Relaxed);
+        // from the view of the player who moved into the last node of the path
+        let mut value = loop {
+            let node = self.arena.get(idx);
+            if let Some(value) = node.proof().value() {
+                break value;
+            }
+            let mover = b.player().next();
+            match b.result() {
+                GameResult::Won(winner) if winner == mover => {
+                    node.set_proof(Proof::Win);
+                    break 1.0;
+                }
+                GameResult::Won(_) => break -1.0,
+                GameResult::Draw => {
+                    node.set_proof(Proof::Draw);
+                    break 0.0;
+                }
+                GameResult::Ongoing => {}
+            }
+            match node
+                .children()
+                .map(|children| self.select(node, children, config))
+            {
+                Some(Some(child_idx)) => {
+                    idx = child_idx;
+                    let child = self.arena.get(idx);
+                    child.n.fetch_add(1, Ordering::Relaxed);
+                    child.add_w(-VIRTUAL_LOSS);
+                    b.make(child.action.load(Ordering::Relaxed));
+                    path.push(idx);
+                }
+                // the children decide this node but the proof has not reached it yet
+                Some(None) => break self.resolve(node).value().unwrap_or(0.0),
+                None => {
+                    let value = -playout(&b, cells) as f64;
+                    if node.n.load(Ordering::Relaxed) >= config.expand_n {
+                        self.expand(idx, &b, Some(config));
+                    }
+                    break value;
+                }
+            }
+        };
+        // a proof moves up for as long as each parent is decided by it
+        let mut solving = self.arena.get(idx).proof() != Proof::Unknown;
+        // the cells each player filled below the node being backed up, indexed by Player
+        let mut filled = [0u64; 2];
+        let mut side = b.player() as usize;
+        for (i, &cell) in cells.iter().enumerate() {
+            filled[side ^ (i % 2)] |= 1 << cell;
+        }
+        for (i, &idx) in path.iter().enumerate().rev() {
+            let node = self.arena.get(idx);
+            let refund = if i == 0 { 0.0 } else { VIRTUAL_LOSS };
+            node.add_w(value + refund);
+            add_f64(&node.w2, value * value);
+            if solving && i + 1 < path.len() {
+                solving = self.resolve(node) != Proof::Unknown;
+            }
+            if config.rave != Rave::Off {
+                self.update_amaf(node, filled[side], -value);
+            }
+            side ^= 1;
+            filled[side] |= 1 << node.cell.load(Ordering::Relaxed);
+            value = -value;
+        }
+        for &idx in cursor.ancestors.iter().rev() {
+            let node = self.arena.get(idx);
+            node.n.fetch_add(1, Ordering::Relaxed);
+            node.add_w(value);
+            add_f64(&node.w2, value * value);
+            if solving {
+                solving = self.resolve(node) != Proof::Unknown;
+            }
+            value = -value;
+        }
+    }
+
+    // every child whose cell the player to move here filled later in the simulation gets the
+    // value as if it had been played first
+    fn update_amaf(&self, node: &Node, filled: u64, value: f64) {
+        for idx in node.children().unwrap_or(0..0) {
+            let child = self.arena.get(idx);
+            if filled & (1 << child.cell.load(Ordering::Relaxed)) != 0 {
+                child.amaf_n.fetch_add(1, Ordering::Relaxed);
+                add_f64(&child.amaf_w, value);
+            }
+        }
+    }
+
+    // a child that wins for the player to move here makes this node a loss; when every child
+    // is solved this node takes the best of them
+    fn resolve(&self, node: &Node) -> Proof {
+        let mut unknown = false;
+        let mut draw = false;
+        for idx in node.children().unwrap_or(0..0) {
+            match self.arena.get(idx).proof() {
+                Proof::Win => {
+                    node.set_proof(Proof::Loss);
+                    return Proof::Loss;
+                }
+                Proof::Draw => draw = true,
+                Proof::Unknown => unknown = true,
+                Proof::Loss => {}
+            }
+        }
+        let proof = if unknown {
+            Proof::Unknown
+        } else if draw {
+            Proof::Draw
+        } else {
+            Proof::Win
+        };
+        node.set_proof(proof);
+        return proof;
+    }
+
+    // solved children are skipped; None when a child wins or every child is solved, which
+    // solves this node
+    fn select(
+        &self,
+        node: &Node,
+        children: std::ops::Range<u32>,
+        config: &MctsConfig,
+    ) -> Option<u32> {
+        let n = node.n.load(Ordering::Relaxed) as f64;
+        let mut best = None;
+        let mut max_score = f64::MIN;
+        for idx in children {
+            let child = self.arena.get(idx);
+            match child.proof() {
+                Proof::Unknown => {}
+                Proof::Win => return None,
+                _ => continue,
+            }
+            let score = child.get_score(n, config);
+            if score > max_score {
+                max_score = score;
+                best = Some(idx);
+            }
+        }
+        return best;
+    }
+
+    // only the thread that moves the node out of LEAF expands it; children that are symmetric
+    // to an earlier sibling are left out. Priors are only worth evaluating for PUCT. False
+    // when the arena is full and the node stays a leaf
+    fn expand(&self, idx: u32, b: &Board, config: Option<&MctsConfig>) -> bool {
+        let node = self.arena.get(idx);
+        if node
+            .state
+            .compare_exchange(LEAF, EXPANDING, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return true;
+        }
+        let mut actions = [0u8; 16];
+        let mut keys = [0u64; 16];
+        let mut count = 0;
+        for action in b.actions() {
+            let key = b.next(action).canonical_key();
+            if !keys[..count].contains(&key) {
+                actions[count] = action;
+                keys[count] = key;
+                count += 1;
+            }
+        }
+        let actions = &actions[..count];
+        let priors = match config {
+            Some(config) if config.selection == Selection::Puct => priors(b, actions),
+            _ => vec![1.0 / count as f32; count],
+        };
+        let first = match self.arena.alloc(count) {
+            Some(first) => first,
+            None => {
+                node.state.store(LEAF, Ordering::Relaxed);
+                return false;
+            }
+        };
+        for (i, &action) in actions.iter().enumerate() {
+            let cell = b.landing_layer(action).unwrap() * 16 + action;
+            self.arena
+                .get(first + i as u32)
+                .init(action, cell, priors[i]);
+        }
+        node.first_child.store(first, Ordering::Relaxed);
+        node.n_children.store(count as u8, Ordering::Relaxed);
+        node.state.store(EXPANDED, Ordering::Release);
+        return true;
+    }
+}
+
+// a softmax over the static evaluation of each of `actions`; the evaluation does not see
+// that a playable threat of the side to move is a win, so a move that leaves one to the
+// opponent is scored as lost
+pub fn priors(b: &Board, actions: &[u8]) -> Vec<f32> {
+    let weights = EvalWeights::global();
+    let logits: Vec<f64> = actions
+        .iter()
+        .map(|&action| {
+            let next = b.next(action);
+            let score = if next.is_win() {
+                MATE_BOUND
+            } else if next.threats(&next.player()).playable != 0 {
+                -MATE_BOUND
+            } else {
+                -weights.evaluate(&next)
+            };
+            score as f64 / PRIOR_TEMPERATURE
+        })
+        .collect();
+    let max = logits.iter().cloned().fold(f64::MIN, f64::max);
+    let exps: Vec<f64> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f64 = exps.iter().sum();
+    return exps.iter().map(|e| (e / sum) as f32).collect();
+}
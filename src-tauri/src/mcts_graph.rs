@@ -0,0 +1,232 @@
+use super::board::{playout, Board, GameResult};
+use super::mcts::{Proof, Score};
+
+// MCTS on a graph: every position reached by any move order or planar symmetry shares one
+// entry, keyed by Board::canonical_key. Values live on the positions and visit counts on the
+// edges, which is the UCT2 update of Childs, Brodeur and Kocsis: a move is valued by
+// everything known about the position it leads to, but explored by how often it was tried
+// from here. Single-threaded, unlike mcts::Tree.
+
+// moves tried from a position, keyed by the low bits of the child's canonical key; symmetric
+// moves lead to the same child and share an edge
+#[derive(Clone, Copy, Default)]
+struct Edge {
+    child: u32,
+    n: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    key: u64,
+    // zero for an empty slot and for a position that has not had its playout yet
+    n: u32,
+    used: bool,
+    // sum of values from the view of the player who moved into the position
+    w: f64,
+    n_edges: u8,
+    edges: [Edge; 16],
+}
+
+impl Entry {
+    fn edge(&self, child: u64) -> u32 {
+        let child = child as u32;
+        return self.edges[..self.n_edges as usize]
+            .iter()
+            .find(|e| e.child == child)
+            .map_or(0, |e| e.n);
+    }
+
+    fn add_edge(&mut self, child: u64) {
+        let child = child as u32;
+        let n_edges = self.n_edges as usize;
+        match self.edges[..n_edges].iter_mut().find(|e| e.child == child) {
+            Some(edge) => edge.n += 1,
+            None if n_edges < 16 => {
+                self.edges[n_edges] = Edge { child: child, n: 1 };
+                self.n_edges += 1;
+            }
+            None => {}
+        }
+    }
+}
+
+// two slots per bucket; a new position takes an empty slot or the one with fewer visits, so
+// the table keeps the positions that most of the search went through
+pub struct Graph {
+    table: Vec<Entry>,
+    // the position being searched is never replaced
+    root: u64,
+}
+
+impl Graph {
+    // a table that fits in `memory` bytes
+    pub fn new(memory: usize) -> Self {
+        let slots = (memory / std::mem::size_of::<Entry>()).max(2) & !1;
+        return Graph {
+            table: vec![Entry::default(); slots],
+            root: 0,
+        };
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.table.len();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.table.iter().filter(|e| e.used).count();
+    }
+
+    // visits of a position, or of any position symmetric to it
+    pub fn visits(&self, board: &Board) -> u32 {
+        return self
+            .find(board.canonical_key())
+            .map_or(0, |idx| self.table[idx].n);
+    }
+
+    fn bucket(&self, key: u64) -> usize {
+        return (key % (self.table.len() / 2) as u64) as usize * 2;
+    }
+
+    fn find(&self, key: u64) -> Option<usize> {
+        let bucket = self.bucket(key);
+        return (bucket..bucket + 2).find(|i| self.table[*i].used && self.table[*i].key == key);
+    }
+
+    fn insert(&mut self, key: u64) -> usize {
+        if let Some(idx) = self.find(key) {
+            return idx;
+        }
+        let bucket = self.bucket(key);
+        let idx = if !self.table[bucket].used {
+            bucket
+        } else if !self.table[bucket + 1].used || self.table[bucket].key == self.root {
+            bucket + 1
+        } else if self.table[bucket + 1].key == self.root {
+            bucket
+        } else if self.table[bucket].n <= self.table[bucket + 1].n {
+            bucket
+        } else {
+            bucket + 1
+        };
+        self.table[idx] = Entry {
+            key: key,
+            used: true,
+            ..Entry::default()
+        };
+        return idx;
+    }
+
+    // statistics are kept between searches, so the graph can follow a game
+    pub fn search(&mut self, board: &Board, search_n: usize) -> Vec<Score> {
+        self.root = board.canonical_key();
+        self.insert(self.root);
+        if board.result() == GameResult::Ongoing {
+            let mut path = Vec::new();
+            let mut cells = Vec::new();
+            for _ in 0..search_n {
+                self.simulate(board, &mut path, &mut cells);
+            }
+        }
+
+        let root = &self.table[self.find(self.root).unwrap()];
+        let n = root.n as f32;
+        let mut scores = Vec::new();
+        let mut seen = Vec::new();
+        for action in board.actions() {
+            let next = board.next(action);
+            let child = next.canonical_key();
+            if seen.contains(&child) {
+                continue;
+            }
+            seen.push(child);
+            let na = root.edge(child) as f32;
+            let q = match self.find(child) {
+                Some(idx) if self.table[idx].n > 0 => self.table[idx].w / self.table[idx].n as f64,
+                _ => 0.0,
+            };
+            scores.push(Score {
+                action: action,
+                score: na / n.max(1.0),
+                q: q as f32,
+                na: na,
+                n: n,
+                proof: if next.is_win() {
+                    Proof::Win
+                } else {
+                    Proof::Unknown
+                },
+            });
+        }
+        return scores;
+    }
+
+    fn simulate(&mut self, board: &Board, path: &mut Vec<u64>, cells: &mut Vec<u8>) {
+        let mut b = board.clone();
+        path.clear();
+        path.push(self.root);
+        // from the view of the player who moved into the last position of the path
+        let mut value = loop {
+            let mover = b.player().next();
+            match b.result() {
+                GameResult::Won(winner) if winner == mover => break 1.0,
+                GameResult::Won(_) => break -1.0,
+                GameResult::Draw => break 0.0,
+                GameResult::Ongoing => {}
+            }
+            let key = *path.last().unwrap();
+            let idx = match self.find(key) {
+                Some(idx) if self.table[idx].n > 0 => idx,
+                // a new position, or one that was replaced on the way down
+                _ => {
+                    cells.clear();
+                    break -playout(&b, cells) as f64;
+                }
+            };
+            let (action, child) = self.select(&mut b, &self.table[idx]);
+            b.make(action);
+            self.insert(child);
+            path.push(child);
+        };
+        for i in (0..path.len()).rev() {
+            if let Some(idx) = self.find(path[i]) {
+                self.table[idx].n += 1;
+                self.table[idx].w += value;
+            }
+            if i > 0 {
+                if let Some(idx) = self.find(path[i - 1]) {
+                    self.table[idx].add_edge(path[i]);
+                }
+            }
+            value = -value;
+        }
+    }
+
+    // UCB1 on the child position's value and the edge's visits; untried moves come first
+    fn select(&self, b: &mut Board, entry: &Entry) -> (u8, u64) {
+        let parent_n = (entry.n as f64).ln();
+        let mut best = (0, 0);
+        let mut max_score = f64::MIN;
+        for action in b.actions() {
+            b.make(action);
+            let child = b.canonical_key();
+            b.unmake();
+            let edge_n = entry.edge(child);
+            let score = if edge_n == 0 {
+                f64::MAX
+            } else {
+                let q = match self.find(child) {
+                    Some(idx) if self.table[idx].n > 0 => {
+                        self.table[idx].w / self.table[idx].n as f64
+                    }
+                    _ => 0.0,
+                };
+                q + (2.0 * parent_n / edge_n as f64).sqrt()
+            };
+            if score > max_score {
+                max_score = score;
+                best = (action, child);
+            }
+        }
+        return best;
+    }
+}
@@ -0,0 +1,396 @@
+use super::board::{is_win_through, Board, MateNode, MateReply, CELL_LINES};
+use super::eval::EvalWeights;
+use rand::Rng;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// score of a win on the board itself; a win k plies ahead scores WIN - k
+pub const WIN: i32 = 10000;
+// anything beyond this is a forced win or loss
+pub const MATE_BOUND: i32 = WIN - 64;
+
+// memory of the transposition table of Searcher::new
+pub const DEFAULT_TABLE_MEMORY: usize = 1 << 24;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+enum Bound {
+    #[default]
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct Entry {
+    ckey: u64,
+    // the uncanonicalized key, so the stored action is only reused in the same orientation
+    key: u64,
+    // zero for an empty slot; nothing is stored at depth 0
+    depth: u8,
+    score: i32,
+    bound: Bound,
+    action: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub action: u8,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    // plies until the side to move wins
+    pub fn mate_in(&self) -> Option<u8> {
+        if self.score > MATE_BOUND {
+            return Some((WIN - self.score) as u8);
+        }
+        return None;
+    }
+
+    // plies until the side to move loses
+    pub fn mated_in(&self) -> Option<u8> {
+        if self.score < -MATE_BOUND {
+            return Some((WIN + self.score) as u8);
+        }
+        return None;
+    }
+}
+
+// how long an iterative-deepening search may run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Budget {
+    Time(Duration),
+    Nodes(u64),
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Budget::Time(time) => write!(f, "{}ms", time.as_millis()),
+            Budget::Nodes(nodes) => write!(f, "{}n", nodes),
+        }
+    }
+}
+
+// mate scores are stored relative to the node so they stay valid at any ply
+fn to_table(score: i32, ply: u8) -> i32 {
+    if score > MATE_BOUND {
+        return score + ply as i32;
+    } else if score < -MATE_BOUND {
+        return score - ply as i32;
+    }
+    return score;
+}
+
+fn from_table(score: i32, ply: u8) -> i32 {
+    if score > MATE_BOUND {
+        return score - ply as i32;
+    } else if score < -MATE_BOUND {
+        return score + ply as i32;
+    }
+    return score;
+}
+
+// negamax alpha-beta with a transposition table keyed by Board::canonical_key. The table has
+// a fixed size and two slots per bucket: the first keeps the deepest entry, the second always
+// takes the newest
+pub struct Searcher {
+    table: Vec<Entry>,
+    pub nodes: u64,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    stopped: bool,
+    weights: EvalWeights,
+}
+
+impl Searcher {
+    pub fn new() -> Self {
+        return Searcher::with_weights(EvalWeights::global().clone());
+    }
+
+    pub fn with_weights(weights: EvalWeights) -> Self {
+        return Searcher::with_table(weights, DEFAULT_TABLE_MEMORY);
+    }
+
+    // a table of the largest power of two of entries that fits in `memory` bytes
+    pub fn with_table(weights: EvalWeights, memory: usize) -> Self {
+        let slots = (memory / std::mem::size_of::<Entry>()).max(2);
+        let slots = 1 << slots.ilog2();
+        return Searcher {
+            table: vec![Entry::default(); slots],
+            nodes: 0,
+            deadline: None,
+            node_limit: None,
+            stopped: false,
+            weights: weights,
+        };
+    }
+
+    // a table sized for a search of `depth` plies, so a shallow search doesn't allocate and
+    // clear DEFAULT_TABLE_MEMORY on every call
+    pub fn for_depth(depth: u8) -> Self {
+        let entries = 1usize << (2 * depth as usize + 4).min(24);
+        let memory = (entries * std::mem::size_of::<Entry>()).min(DEFAULT_TABLE_MEMORY);
+        return Searcher::with_table(EvalWeights::global().clone(), memory);
+    }
+
+    // a table of at most one entry per node of the budget; a time budget gets the default
+    pub fn for_budget(budget: Budget) -> Self {
+        let memory = match budget {
+            Budget::Time(_) => DEFAULT_TABLE_MEMORY,
+            Budget::Nodes(nodes) => (nodes as usize)
+                .saturating_mul(std::mem::size_of::<Entry>())
+                .min(DEFAULT_TABLE_MEMORY),
+        };
+        return Searcher::with_table(EvalWeights::global().clone(), memory);
+    }
+
+    // deepens one ply at a time and returns the deepest iteration that finished;
+    // depth 1 always finishes so there is a move even on a tiny budget
+    pub fn iterative(&mut self, board: &Board, budget: Budget) -> SearchResult {
+        let empty = 64 - (board.black | board.white).count_ones() as u8;
+        let mut best = self.search(board, 1);
+        match budget {
+            Budget::Time(time) => self.deadline = Some(Instant::now() + time),
+            Budget::Nodes(nodes) => self.node_limit = Some(self.nodes + nodes),
+        }
+        for depth in 2..=empty {
+            if best.mate_in().is_some() || best.mated_in().is_some() {
+                break;
+            }
+            let result = self.search(board, depth);
+            if self.stopped {
+                break;
+            }
+            best = result;
+        }
+        best.nodes = self.nodes;
+        self.deadline = None;
+        self.node_limit = None;
+        self.stopped = false;
+        return best;
+    }
+
+    fn out_of_budget(&mut self) -> bool {
+        if !self.stopped && self.nodes & 1023 == 0 {
+            let late = self.deadline.map_or(false, |d| Instant::now() >= d);
+            let spent = self.node_limit.map_or(false, |n| self.nodes >= n);
+            self.stopped = late || spent;
+        }
+        return self.stopped;
+    }
+
+    pub fn clear(&mut self) {
+        self.table.fill(Entry::default());
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.table.len();
+    }
+
+    // entries in use
+    pub fn len(&self) -> usize {
+        return self.table.iter().filter(|e| e.depth > 0).count();
+    }
+
+    fn bucket(&self, ckey: u64) -> usize {
+        return ckey as usize & (self.table.len() - 1) & !1;
+    }
+
+    fn probe(&self, ckey: u64) -> Option<&Entry> {
+        let bucket = self.bucket(ckey);
+        return self.table[bucket..bucket + 2]
+            .iter()
+            .find(|e| e.depth > 0 && e.ckey == ckey);
+    }
+
+    fn store(&mut self, entry: Entry) {
+        let bucket = self.bucket(entry.ckey);
+        let deep = &self.table[bucket];
+        if deep.depth == 0 || deep.ckey == entry.ckey || entry.depth >= deep.depth {
+            self.table[bucket] = entry;
+        } else {
+            self.table[bucket + 1] = entry;
+        }
+    }
+
+    // best action at the given depth, ties broken at random
+    pub fn search(&mut self, board: &Board, depth: u8) -> SearchResult {
+        let depth = depth.max(1);
+        let mut b = board.clone();
+        let (actions, n) = self.ordered_actions(&b, None);
+        let mut best = -WIN - 1;
+        let mut ties: Vec<u8> = Vec::new();
+        for &action in actions[..n].iter() {
+            b.make(action);
+            // a window just below the best so far keeps equal moves exact
+            let score = -self.negamax(&mut b, depth - 1, -WIN - 1, -(best - 1), 1);
+            b.unmake();
+            if self.stopped {
+                break;
+            }
+            if score > best {
+                best = score;
+                ties = vec![action];
+            } else if score == best {
+                ties.push(action);
+            }
+        }
+        if ties.is_empty() {
+            // stopped before the first move finished; iterative() drops this result
+            ties.push(actions[0]);
+        }
+        let mut rng = rand::thread_rng();
+        return SearchResult {
+            action: ties[rng.gen::<usize>() % ties.len()],
+            score: best,
+            depth: depth,
+            nodes: self.nodes,
+        };
+    }
+
+    // a forced win for the side to move within depth plies, as (plies, first action)
+    pub fn find_mate(&mut self, board: &Board, depth: u8) -> Option<(u8, u8)> {
+        let result = self.search(board, depth);
+        return result.mate_in().map(|plies| (plies, result.action));
+    }
+
+    // the proof tree of a forced win within depth plies, one search per attacker node
+    pub fn mate_tree(&mut self, board: &Board, depth: u8) -> Option<MateNode> {
+        let (plies, action) = self.find_mate(board, depth)?;
+        let mut b = board.next(action);
+        let mut replies = Vec::new();
+        if !b.is_win() {
+            for reply in b.actions() {
+                b.make(reply);
+                let answer = self.mate_tree(&b, plies - 2)?;
+                b.unmake();
+                replies.push(MateReply {
+                    action: reply,
+                    answer: answer,
+                });
+            }
+        }
+        return Some(MateNode {
+            action: action,
+            depth: plies,
+            replies: replies,
+        });
+    }
+
+    fn negamax(&mut self, b: &mut Board, depth: u8, alpha: i32, beta: i32, ply: u8) -> i32 {
+        self.nodes += 1;
+        // the caller throws the whole iteration away, so any value will do
+        if self.out_of_budget() {
+            return 0;
+        }
+        if b.is_win() {
+            return -(WIN - ply as i32);
+        } else if b.is_full() {
+            return 0;
+        } else if depth == 0 {
+            return self.evaluate(b);
+        }
+
+        let (mut alpha, mut beta) = (alpha, beta);
+        let alpha_orig = alpha;
+        let ckey = b.canonical_key();
+        let mut table_action = None;
+        if let Some(entry) = self.probe(ckey) {
+            if entry.key == b.key() {
+                table_action = Some(entry.action);
+            }
+            if entry.depth >= depth {
+                let score = from_table(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower => alpha = alpha.max(score),
+                    Bound::Upper => beta = beta.min(score),
+                }
+                if alpha >= beta {
+                    return score;
+                }
+            }
+        }
+
+        let (actions, n) = self.ordered_actions(b, table_action);
+        let mut best = -WIN - 1;
+        let mut best_action = actions[0];
+        for &action in actions[..n].iter() {
+            b.make(action);
+            let score = -self.negamax(b, depth - 1, -beta, -alpha, ply + 1);
+            b.unmake();
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_action = action;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= alpha_orig {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.store(Entry {
+            ckey: ckey,
+            key: b.key(),
+            depth: depth,
+            score: to_table(best, ply),
+            bound: bound,
+            action: best_action,
+        });
+        return best;
+    }
+
+    fn evaluate(&self, b: &Board) -> i32 {
+        return self.weights.evaluate(b);
+    }
+
+    // winning moves, then blocks, then the table move, then cells on more lines
+    fn ordered_actions(&self, b: &Board, table_action: Option<u8>) -> ([u8; 16], usize) {
+        let own = b.stones(&b.player());
+        let other = b.stones(&b.player().next());
+        let landing = b.playable_cells();
+        let mut actions = [0u8; 16];
+        let mut priorities = [0i32; 16];
+        let mut n = 0;
+        for action in b.actions() {
+            let cell = (landing & (0x0001000100010001u64 << action)).trailing_zeros() as u8;
+            let mut priority = CELL_LINES[cell as usize]
+                .iter()
+                .filter(|l| **l != 0)
+                .count() as i32;
+            if is_win_through(own | (1 << cell), cell) {
+                priority += 1000;
+            } else if is_win_through(other | (1 << cell), cell) {
+                priority += 500;
+            } else if Some(action) == table_action {
+                priority += 100;
+            }
+            // insertion sort, highest priority first
+            let mut i = n;
+            while i > 0 && priorities[i - 1] < priority {
+                actions[i] = actions[i - 1];
+                priorities[i] = priorities[i - 1];
+                i -= 1;
+            }
+            actions[i] = action;
+            priorities[i] = priority;
+            n += 1;
+        }
+        return (actions, n);
+    }
+}
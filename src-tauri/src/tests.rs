@@ -1,9 +1,14 @@
 use crate::board;
+use crate::eval;
+use crate::mcts;
+use crate::mcts_graph;
+use crate::search;
+use crate::tss;
 // use test::Bencher;
 
 #[cfg(test)]
 pub mod tests {
-    use super::{board, board::_is_win_board};
+    use super::{board, board::_is_win_board, eval, mcts, mcts_graph, search, tss};
     use std::time::{Duration, Instant};
 
     #[test]
@@ -19,6 +24,61 @@ pub mod tests {
         assert_eq!(board.white, 0x0001000000010000);
     }
 
+    #[test]
+    fn try_next_test() {
+        let mut board = board::Board::new();
+        assert_eq!(
+            board.try_next(16).err(),
+            Some(board::MoveError::OutOfRange(16))
+        );
+        for _ in 0..4 {
+            board = board.try_next(3).unwrap();
+        }
+        assert_eq!(
+            board.try_next(3).err(),
+            Some(board::MoveError::ColumnFull(3))
+        );
+        let mut board = board::Board::new();
+        for action in [0, 4, 1, 5, 2, 6, 3] {
+            board = board.try_next(action).unwrap();
+        }
+        assert!(board.is_win());
+        assert_eq!(board.try_next(7).err(), Some(board::MoveError::GameOver));
+    }
+
+    #[test]
+    #[should_panic(expected = "illegal action")]
+    fn illegal_make_test() {
+        let mut board = board::Board::new();
+        for _ in 0..4 {
+            board.make(3);
+        }
+        assert!(!board.is_legal(3));
+        assert!(!board.is_legal(16));
+        assert!(board.is_legal(2));
+        board.make(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "illegal action 64")]
+    fn out_of_range_make_test() {
+        board::Board::new().make(64);
+    }
+
+    #[test]
+    fn result_test() {
+        let mut board = board::Board::new();
+        assert_eq!(board.result(), board::GameResult::Ongoing);
+        for action in [0, 4, 1, 5, 2, 6, 8, 7] {
+            board = board.next(action);
+        }
+        assert_eq!(board.result(), board::GameResult::Won(board::Player::White));
+        assert_eq!(board.winner(), Some(board::Player::White));
+        assert!(board.is_won_by(&board::Player::White));
+        assert!(!board.is_won_by(&board::Player::Black));
+        assert!(!board.is_draw());
+    }
+
     #[test]
     fn is_win_test() {
         assert!(_is_win_board(0xf000000000000000));
@@ -34,6 +94,619 @@ pub mod tests {
         // assert!(_is_win_board(0x0000000000000000));
     }
 
+    #[test]
+    fn lines_test() {
+        let lines = board::LINES;
+        for line in lines.iter() {
+            assert_eq!(line.count_ones(), 4);
+            assert!(_is_win_board(*line));
+        }
+        for i in 0..76 {
+            for j in (i + 1)..76 {
+                assert_ne!(lines[i], lines[j]);
+            }
+        }
+        for cell in 0..64 {
+            let through = lines.iter().filter(|l| (*l >> cell) & 1 == 1).count();
+            assert!(through == 4 || through == 7);
+        }
+    }
+
+    #[test]
+    fn winning_lines_test() {
+        let mut board = board::Board::new();
+        for action in [0, 4, 1, 5, 2, 6, 3] {
+            board = board.next(action);
+        }
+        assert_eq!(
+            board.winning_lines(&board::Player::Black),
+            vec![0x000000000000000f]
+        );
+        assert_eq!(board.winning_cells(), vec![0, 1, 2, 3]);
+        assert!(board.winning_lines(&board::Player::White).is_empty());
+    }
+
+    #[test]
+    fn parse_test() {
+        let board: board::Board = "0 5 5 12".parse().unwrap();
+        assert_eq!(board.black, 0x0000000000200001);
+        assert_eq!(board.white, 0x0000000000001020);
+        assert!(board.is_black());
+        assert_eq!(
+            board.to_board_string().parse::<board::Board>(),
+            Ok(board.clone())
+        );
+        assert_eq!(board.to_string().parse::<board::Board>(), Ok(board.clone()));
+
+        let board: board::Board = "0 5 5".parse().unwrap();
+        assert!(!board.is_black());
+        assert_eq!(
+            board.to_board_string().parse::<board::Board>(),
+            Ok(board.clone())
+        );
+        assert_eq!(board.to_string().parse::<board::Board>(), Ok(board));
+    }
+
+    #[test]
+    fn parse_error_test() {
+        use board::{MoveError, ParseBoardError};
+        assert_eq!(
+            "0 16".parse::<board::Board>(),
+            Err(ParseBoardError::Move(MoveError::OutOfRange(16)))
+        );
+        assert_eq!(
+            "0 a".parse::<board::Board>(),
+            Err(ParseBoardError::InvalidNumber(String::from("a")))
+        );
+        assert_eq!(
+            "65536,0".parse::<board::Board>(),
+            Err(ParseBoardError::Floating(16))
+        );
+        assert_eq!(
+            "3,0".parse::<board::Board>(),
+            Err(ParseBoardError::StoneCount { black: 2, white: 0 })
+        );
+        assert_eq!("1,1".parse::<board::Board>(), Err(ParseBoardError::Overlap));
+        // a row of four on the first layer for each side
+        assert_eq!(
+            format!("{},{}", 0xf, 0xf0).parse::<board::Board>(),
+            Err(ParseBoardError::BothWon)
+        );
+        // Black completed the row and White moved after it
+        assert_eq!(
+            format!("{},{}", 0xf, 0x170).parse::<board::Board>(),
+            Err(ParseBoardError::WonBySideToMove)
+        );
+        assert!(format!("{},{}", 0xf, 0x70)
+            .parse::<board::Board>()
+            .unwrap()
+            .is_win());
+        let mut s = String::from("O");
+        s += &"-".repeat(63);
+        assert!(board::Board::from_board_string(&(s.clone() + "W")).is_ok());
+        assert_eq!(
+            board::Board::from_board_string(&(s + "B")),
+            Err(ParseBoardError::StoneCount { black: 1, white: 0 })
+        );
+    }
+
+    #[test]
+    fn serde_board_test() {
+        let board: board::Board = "0 5 5 12".parse().unwrap();
+        let json = serde_json::to_value(&board).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "black": [
+                    {"layer": 0, "row": 0, "column": 0},
+                    {"layer": 1, "row": 1, "column": 1},
+                ],
+                "white": [
+                    {"layer": 0, "row": 1, "column": 1},
+                    {"layer": 0, "row": 3, "column": 0},
+                ],
+                "player": "Black",
+            })
+        );
+        assert_eq!(serde_json::from_value::<board::Board>(json).unwrap(), board);
+
+        let floating = serde_json::json!({
+            "black": [{"layer": 1, "row": 0, "column": 0}],
+            "white": [],
+            "player": "White",
+        });
+        assert!(serde_json::from_value::<board::Board>(floating).is_err());
+    }
+
+    #[test]
+    fn serde_record_test() {
+        let mut record = board::Record::new();
+        for action in [0, 5, 5, 12] {
+            record.push(action).unwrap();
+        }
+        record.back();
+        let json = serde_json::to_string(&record).unwrap();
+        let restored: board::Record = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.get_last_board(), record.get_last_board());
+
+        let bad = r#"{"moves": [0, 0, 0, 0, 0], "cursor": 4, "initial": false}"#;
+        assert!(serde_json::from_str::<board::Record>(bad).is_err());
+        let bad = r#"{"moves": [0, 5, 5], "cursor": 2, "initial": true}"#;
+        assert!(serde_json::from_str::<board::Record>(bad).is_err());
+        let initial = r#"{"moves": [0, 5, 5], "cursor": 0, "initial": true}"#;
+        let restored: board::Record = serde_json::from_str(initial).unwrap();
+        assert_eq!(restored.get_last_board(), board::Board::new());
+    }
+
+    #[test]
+    fn game_state_test() {
+        let mut record = board::Record::new();
+        let state = record.game_state();
+        assert_eq!(state.ply, 0);
+        assert_eq!(state.legal_moves.len(), 16);
+        for action in [0, 4, 0, 5, 1, 6, 2, 7] {
+            record.push(action).unwrap();
+        }
+        let state = record.game_state();
+        assert_eq!(state.ply, 8);
+        assert_eq!(state.total_plies, 8);
+        assert_eq!(state.result, board::GameResult::Won(board::Player::White));
+        assert!(state.legal_moves.is_empty());
+        assert_eq!(state.winning_cells.len(), 4);
+        assert_eq!(record.push(3), Err(board::MoveError::GameOver));
+
+        record.back();
+        let state = record.game_state();
+        assert_eq!(state.ply, 7);
+        assert_eq!(state.total_plies, 8);
+        assert_eq!(state.player, board::Player::White);
+        let column0 = state.legal_moves.iter().find(|m| m.action == 0).unwrap();
+        assert_eq!(column0.layer, 2);
+    }
+
+    #[test]
+    fn make_unmake_test() {
+        let mut board = board::Board::new();
+        let mut history = vec![board.clone()];
+        while board.result() == board::GameResult::Ongoing {
+            let action = board::get_random(&board);
+            board.make(action);
+            history.push(board.clone());
+        }
+        history.pop();
+        while let Some(expected) = history.pop() {
+            assert!(board.unmake());
+            assert_eq!(board, expected);
+        }
+        assert!(!board.unmake());
+    }
+
+    #[test]
+    fn actions_test() {
+        let mut board = board::Board::new();
+        assert_eq!(board.actions().len(), 16);
+        for _ in 0..4 {
+            board.make(5);
+        }
+        assert_eq!(board.last_cell(), Some(53));
+        assert_eq!(board.actions().len(), 15);
+        assert!(board.actions().all(|action| action != 5));
+        assert_eq!(board.actions().collect::<Vec<u8>>(), board.valid_actions());
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            assert_ne!(board.random_action(&mut rng), 5);
+        }
+    }
+
+    #[test]
+    fn zobrist_test() {
+        for _ in 0..20 {
+            let mut board = board::Board::new();
+            let mut keys = vec![board.key()];
+            while board.result() == board::GameResult::Ongoing {
+                board.make(board::get_random(&board));
+                let parsed = board::Board::from_bitboards(board.black, board.white).unwrap();
+                assert_eq!(board.key(), parsed.key());
+                assert_eq!(board.canonical_key(), parsed.canonical_key());
+                keys.push(board.key());
+            }
+            while board.unmake() {
+                keys.pop();
+                assert_eq!(board.key(), *keys.last().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_key_test() {
+        for _ in 0..20 {
+            let mut board = board::Board::new();
+            for _ in 0..9 {
+                if board.is_win() {
+                    break;
+                }
+                board.make(board::get_random(&board));
+            }
+            for sym in 0..8 {
+                let image = |stones: u64| {
+                    board::mask_to_cells(stones)
+                        .iter()
+                        .map(|cell| {
+                            let column = board::planar_action(sym, cell % 16);
+                            1u64 << (cell - cell % 16 + column)
+                        })
+                        .sum()
+                };
+                let image =
+                    board::Board::from_bitboards(image(board.black), image(board.white)).unwrap();
+                assert_eq!(image.canonical_key(), board.canonical_key());
+                assert_eq!(image.hash(), board.hash());
+                assert!(board.symmetry_to(&image).is_some());
+            }
+        }
+
+        // the planar symmetries are 8 distinct maps that keep lines on lines
+        let mut maps = std::collections::HashSet::new();
+        for sym in 0..8 {
+            let map: Vec<u8> = (0..16).map(|a| board::planar_action(sym, a)).collect();
+            maps.insert(map);
+            for line in board::LINES.iter() {
+                let image: u64 = board::mask_to_cells(*line)
+                    .iter()
+                    .map(|cell| 1u64 << (cell - cell % 16 + board::planar_action(sym, cell % 16)))
+                    .sum();
+                assert!(board::LINES.contains(&image));
+            }
+        }
+        assert_eq!(maps.len(), 8);
+    }
+
+    #[test]
+    fn cell_lines_test() {
+        for cell in 0..64u8 {
+            let lines = board::CELL_LINES[cell as usize];
+            let count = lines.iter().filter(|l| **l != 0).count();
+            assert!(count == 4 || count == 7);
+            for line in lines.iter().take(count) {
+                assert!(board::LINES.contains(line));
+                assert!(board::is_win_through(*line, cell));
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_win_test() {
+        for _ in 0..100 {
+            let mut board = board::Board::new();
+            loop {
+                board.make(board::get_random(&board));
+                let last = if board.is_black() {
+                    board.white
+                } else {
+                    board.black
+                };
+                assert_eq!(board.is_win(), _is_win_board(last));
+                if board.result() != board::GameResult::Ongoing {
+                    break;
+                }
+            }
+            board.unmake();
+            assert!(!board.is_win());
+        }
+    }
+
+    #[test]
+    fn threats_test() {
+        // Black holds cells 0, 1 and 2 of the bottom row
+        let board: board::Board = "0 4 1 4 2 8".parse().unwrap();
+        let threats = board.threats(&board::Player::Black);
+        assert_eq!(threats.playable, 1 << 3);
+        assert_eq!(threats.pending, 0);
+        assert!(board.threats(&board::Player::White).all() == 0);
+
+        let counts = board.line_counts();
+        assert_eq!(counts.count_open(&board::Player::Black, 3), 1);
+        assert_eq!(board.open_lines(&board::Player::Black, 3), vec![0xf]);
+
+        // Black needs cell 19, which sits on top of the empty cell 3
+        let board: board::Board = "0 1 0 2 1 5 2".parse().unwrap();
+        let threats = board.threats(&board::Player::Black);
+        assert_eq!(threats.playable, 0);
+        assert_eq!(threats.pending, 1 << 19);
+        let cells = board.threat_cells();
+        assert_eq!(cells.len(), 1);
+        assert!(!cells[0].playable);
+    }
+
+    fn reference_negamax(b: &mut board::Board, depth: u8, ply: i32) -> i32 {
+        if b.is_win() {
+            return -(search::WIN - ply);
+        } else if b.is_full() {
+            return 0;
+        } else if depth == 0 {
+            return eval::EvalWeights::default().evaluate(b);
+        }
+        let mut best = -search::WIN - 1;
+        for action in b.actions() {
+            b.make(action);
+            best = best.max(-reference_negamax(b, depth - 1, ply + 1));
+            b.unmake();
+        }
+        return best;
+    }
+
+    #[test]
+    fn alphabeta_test() {
+        for _ in 0..30 {
+            let mut b = board::Board::new();
+            for _ in 0..20 {
+                b.make(board::get_random(&b));
+            }
+            if b.result() != board::GameResult::Ongoing {
+                continue;
+            }
+            let weights = eval::EvalWeights::default();
+            let result = search::Searcher::with_weights(weights).search(&b, 3);
+            assert_eq!(result.score, reference_negamax(&mut b.clone(), 3, 0));
+            let mut child = b.next(result.action);
+            assert_eq!(-reference_negamax(&mut child, 2, 1), result.score);
+        }
+    }
+
+    #[test]
+    fn mate_test() {
+        // Black completes the bottom row at once
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        let result = search::Searcher::new().search(&b, 3);
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.action, 3);
+
+        // cell 5 makes threats on row 1 and column 1 at once
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let result = search::Searcher::new().search(&b, 3);
+        assert_eq!(result.mate_in(), Some(3));
+        assert_eq!(result.action, 5);
+        let result = search::Searcher::new().search(&b.next(5), 4);
+        assert_eq!(result.mated_in(), Some(2));
+        assert_eq!(b.has_mate(3), (true, 5));
+        assert_eq!(b.has_mate(1), (false, 0));
+    }
+
+    #[test]
+    fn proof_search_test() {
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        let result = b.proof_search(board::PnsLimit::Nodes(100_000));
+        assert_eq!(result.status, board::ProofStatus::Proven);
+        assert_eq!(result.distance, Some(1));
+        assert_eq!(result.action, Some(3));
+
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let result = b.proof_search(board::PnsLimit::Nodes(1_000_000));
+        assert_eq!(result.status, board::ProofStatus::Proven);
+        assert_eq!(result.distance, Some(3));
+        assert_eq!(result.action, Some(5));
+        // after the mating move the defender is lost whatever they play
+        let result = b.next(5).proof_search(board::PnsLimit::Nodes(1_000_000));
+        assert_eq!(result.status, board::ProofStatus::Disproven);
+
+        let result = board::Board::new().proof_search(board::PnsLimit::Nodes(1000));
+        assert_eq!(result.status, board::ProofStatus::Unknown);
+        assert!(result.nodes <= 1000);
+        let result = board::Board::new().proof_search(board::PnsLimit::Memory(1 << 16));
+        assert_eq!(result.status, board::ProofStatus::Unknown);
+    }
+
+    #[test]
+    fn proof_search_agrees_with_alphabeta() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let mut b = board::Board::new();
+            while b.stones(&board::Player::Black).count_ones() < 10 && !b.is_win() {
+                b.make(b.random_action(&mut rng));
+            }
+            if b.is_win() {
+                continue;
+            }
+            let mate = search::Searcher::new().find_mate(&b, 5);
+            let result = b.proof_search(board::PnsLimit::Nodes(50_000));
+            match result.status {
+                board::ProofStatus::Proven => {
+                    let d = result.distance.unwrap();
+                    let action = result.action.unwrap();
+                    check_mate_tree(&b, result.tree.as_ref().unwrap());
+                    if d <= 5 {
+                        assert!(mate.is_some());
+                    }
+                    // long proofs are too slow to confirm with alpha-beta
+                    if d > 7 {
+                        continue;
+                    }
+                    let check = search::Searcher::new().search(&b, d);
+                    assert_eq!(check.mate_in().map(|m| m <= d), Some(true));
+                    let after = b.next(action);
+                    assert!(
+                        after.is_win() || {
+                            let reply = search::Searcher::new().search(&after, d - 1);
+                            reply.mated_in().is_some()
+                        }
+                    );
+                }
+                board::ProofStatus::Disproven => assert_eq!(mate, None),
+                board::ProofStatus::Unknown => {}
+            }
+        }
+    }
+
+    // every defender reply is answered and every branch ends in a win for the attacker
+    fn check_mate_tree(b: &board::Board, tree: &board::MateNode) {
+        let after = b.next(tree.action);
+        if tree.replies.is_empty() {
+            assert!(after.is_win());
+            assert_eq!(tree.depth, 1);
+            return;
+        }
+        let replies: Vec<u8> = tree.replies.iter().map(|r| r.action).collect();
+        assert_eq!(replies, after.actions().collect::<Vec<u8>>());
+        for reply in tree.replies.iter() {
+            assert!(reply.answer.depth + 2 <= tree.depth);
+            check_mate_tree(&after.next(reply.action), &reply.answer);
+        }
+    }
+
+    #[test]
+    fn mate_tree_test() {
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        let tree = search::Searcher::new().mate_tree(&b, 3).unwrap();
+        assert_eq!(tree.action, 3);
+        assert_eq!(tree.principal_line(), vec![3]);
+
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let tree = search::Searcher::new().mate_tree(&b, 3).unwrap();
+        assert_eq!((tree.action, tree.depth), (5, 3));
+        assert_eq!(tree.replies.len(), 16);
+        check_mate_tree(&b, &tree);
+        let line = tree.principal_line();
+        assert_eq!(line.len(), 3);
+        assert!(b.next(line[0]).next(line[1]).next(line[2]).is_win());
+        assert_eq!(search::Searcher::new().mate_tree(&b, 1), None);
+
+        let result = b.proof_search(board::PnsLimit::Nodes(1_000_000));
+        let pns_tree = result.tree.unwrap();
+        assert_eq!(pns_tree.depth, 3);
+        check_mate_tree(&b, &pns_tree);
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let back: board::MateNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, tree);
+    }
+
+    #[test]
+    fn threat_space_test() {
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let tree = tss::ThreatSpaceSearch::new(100_000).solve(&b, 64).unwrap();
+        assert_eq!((tree.action, tree.depth), (5, 3));
+        check_mate_tree(&b, &tree);
+        assert_eq!(tss::ThreatSpaceSearch::new(100_000).solve(&b, 1), None);
+        assert_eq!(
+            tss::ThreatSpaceSearch::new(100_000).solve(&board::Board::new(), 64),
+            None
+        );
+
+        // a long threat sequence that a depth 5 search cannot see
+        let b: board::Board = "4947803376848,75596321".parse().unwrap();
+        let tree = tss::ThreatSpaceSearch::new(100_000).solve(&b, 64).unwrap();
+        assert!(tree.depth > 5);
+        check_mate_tree(&b, &tree);
+        assert_eq!(search::Searcher::new().find_mate(&b, 5), None);
+
+        // every sequence found must be a real forced win
+        let mut rng = rand::thread_rng();
+        let mut found = 0;
+        for _ in 0..200 {
+            let mut b = board::Board::new();
+            while b.stones(&board::Player::Black).count_ones() < 8 && !b.is_win() {
+                b.make(b.random_action(&mut rng));
+            }
+            if b.is_win() {
+                continue;
+            }
+            if let Some(tree) = tss::ThreatSpaceSearch::new(100_000).solve(&b, 64) {
+                check_mate_tree(&b, &tree);
+                found += 1;
+            }
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn danger_test() {
+        let b: board::Board = "4 3 6 12 1 10 9".parse().unwrap();
+        let passed = b.null_move();
+        assert_eq!(passed.player(), board::Player::Black);
+        assert_eq!(passed.null_move().key(), b.key());
+        assert_ne!(passed.key(), b.key());
+        let limit = board::PnsLimit::Nodes(200_000);
+        // Black threatens the double threat on cell 5
+        assert_eq!(
+            passed.proof_search(limit).status,
+            board::ProofStatus::Proven
+        );
+        for action in b.actions() {
+            if let board::Danger::Losing(plies) = b.danger(action, limit) {
+                let result = search::Searcher::new().search(&b.next(action), plies);
+                assert!(result.mate_in().is_some());
+            }
+        }
+        assert_eq!(b.danger(0, limit), board::Danger::Losing(3));
+        assert_ne!(b.danger(5, limit), board::Danger::Losing(3));
+
+        // a winning move ends the game
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        assert_eq!(b.danger(3, limit), board::Danger::Safe);
+    }
+
+    #[test]
+    fn iterative_test() {
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let result = search::Searcher::new().iterative(&b, search::Budget::Nodes(1_000_000));
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.mate_in(), Some(3));
+        assert_eq!(result.action, 5);
+
+        let b = board::Board::new();
+        let result = search::Searcher::new().iterative(&b, search::Budget::Nodes(5000));
+        assert!(result.depth >= 1);
+        assert!(result.nodes < 10000);
+        let start = Instant::now();
+        let result =
+            search::Searcher::new().iterative(&b, search::Budget::Time(Duration::from_millis(50)));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(b.try_next(result.action).is_ok());
+    }
+
+    #[test]
+    fn search_table_test() {
+        let weights = eval::EvalWeights::default();
+        let searcher = search::Searcher::with_table(weights.clone(), 1000);
+        assert!(searcher.capacity().is_power_of_two());
+        assert!(searcher.capacity() * 24 <= 1000);
+
+        // a tiny table keeps replacing entries but the mate is still found
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let mut searcher = search::Searcher::with_table(weights, 4096);
+        let result = searcher.iterative(&b, search::Budget::Nodes(1_000_000));
+        assert_eq!(result.mate_in(), Some(3));
+        assert_eq!(result.action, 5);
+        assert!(searcher.len() > 0 && searcher.len() <= searcher.capacity());
+        searcher.clear();
+        assert_eq!(searcher.len(), 0);
+
+        // shallow searches and small budgets don't pay for the default table
+        let default = search::Searcher::new().capacity();
+        assert!(search::Searcher::for_depth(3).capacity() < default);
+        assert!(search::Searcher::for_depth(40).capacity() <= default);
+        assert!(search::Searcher::for_budget(search::Budget::Nodes(1000)).capacity() <= 1000);
+        let budget = search::Budget::Time(Duration::from_millis(10));
+        assert_eq!(search::Searcher::for_budget(budget).capacity(), default);
+    }
+
+    #[test]
+    fn eval_test() {
+        let weights = eval::EvalWeights::default();
+        assert_eq!(weights.evaluate(&board::Board::new()), weights.tempo);
+
+        // Black owns a playable threat at cell 3 and White has none
+        let b: board::Board = "0 4 1 4 2 8".parse().unwrap();
+        assert!(weights.evaluate(&b) > weights.playable_threat);
+        assert!(weights.evaluate(&b.next(12)) < 0);
+
+        let json = r#"{"open": [0, 0, 0], "tempo": 7}"#;
+        let weights = eval::EvalWeights::from_json(json).unwrap();
+        assert_eq!(weights.playable_threat, 64);
+        assert_eq!(weights.evaluate(&board::Board::new()), 7);
+        assert!(eval::EvalWeights::from_json("{\"open\": 3}").is_err());
+    }
+
     #[test]
     fn bench_minimaxs() {
         for depth in 1..=5 {
@@ -57,6 +730,306 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn parallel_mcts_test() {
+        let config = mcts::MctsConfig::default();
+        let tree = mcts::Tree::new(board::Board::new());
+        let scores = tree.search(&config, 4000, 4);
+        // every playout goes through the root and one of its moves
+        assert!(scores.iter().all(|s| s.n == 4000.0));
+        let visits: f32 = scores.iter().map(|s| s.na).sum();
+        assert_eq!(visits, 4000.0);
+
+        // a second search continues on the same tree
+        let scores = tree.search(&config, 1000, 2);
+        assert!(scores.iter().all(|s| s.n == 5000.0));
+        assert!(scores.iter().all(|s| s.proof == mcts::Proof::Unknown));
+    }
+
+    #[test]
+    fn mcts_solver_test() {
+        // the winning move is proven on its first visit and the search stops there
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        let scores = mcts::Tree::new(b.clone()).search(&mcts::MctsConfig::default(), 100_000, 2);
+        let win = scores.iter().find(|s| s.action == 3).unwrap();
+        assert_eq!(win.proof, mcts::Proof::Win);
+        assert!(scores[0].n < 10_000.0);
+        assert_eq!(
+            board::mcts_action(&b, &mcts::MctsConfig::default(), 2000),
+            3
+        );
+
+        // White cannot stop both threats, so every White move is proven lost
+        let config = mcts::MctsConfig {
+            expand_n: 1,
+            ..mcts::MctsConfig::default()
+        };
+        let b: board::Board = "4 3 6 12 1 10 9 15 5".parse().unwrap();
+        let scores = mcts::Tree::new(b.clone()).search(&config, 200_000, 2);
+        assert!(scores.iter().all(|s| s.proof == mcts::Proof::Loss));
+        let b: board::Board = "4 3 6 12 1 10 9 15".parse().unwrap();
+        let scores = mcts::Tree::new(b.clone()).search(&config, 200_000, 2);
+        let win = scores.iter().find(|s| s.proof == mcts::Proof::Win).unwrap();
+        assert_eq!(win.action, 5);
+    }
+
+    #[test]
+    fn mcts_arena_test() {
+        // expanding at every visit fills several arena chunks
+        let config = mcts::MctsConfig {
+            expand_n: 1,
+            ..mcts::MctsConfig::default()
+        };
+        let tree = mcts::Tree::new(board::Board::new());
+        let scores = tree.search(&config, 20_000, 3);
+        assert!(tree.len() > 100_000);
+        // moves symmetric to an earlier one share its child: a corner, an edge and a center
+        assert_eq!(scores.len(), 3);
+        let visits: f32 = scores.iter().map(|s| s.na).sum();
+        assert_eq!(visits, 20_000.0);
+        assert!(scores.iter().all(|s| s.q.abs() <= 1.0));
+
+        let b: board::Board = "0 4 1 5 2 8 3".parse().unwrap();
+        assert_ne!(b.result(), board::GameResult::Ongoing);
+        assert!(mcts::Tree::new(b)
+            .search(&mcts::MctsConfig::default(), 100, 1)
+            .is_empty());
+
+        // a full arena stops the tree from growing but not the search
+        let tree = mcts::Tree::with_capacity(board::Board::new(), 100);
+        let scores = tree.search(&config, 5000, 2);
+        assert!(tree.len() <= 100);
+        let visits: f32 = scores.iter().map(|s| s.na).sum();
+        assert_eq!(visits, 5000.0);
+        let deep: board::Board = "0 1 2 3 4 5 6 7".parse().unwrap();
+        assert!(tree.cursor(&deep).is_none());
+    }
+
+    #[test]
+    fn mcts_reuse_test() {
+        let config = mcts::MctsConfig {
+            expand_n: 10,
+            ..mcts::MctsConfig::default()
+        };
+        let tree = mcts::Tree::new(board::Board::new());
+        let scores = tree.search(&config, 3000, 2);
+        let na = scores.iter().find(|s| s.action == 5).unwrap().na;
+        let cursor = tree.cursor(&board::Board::new().next(5)).unwrap();
+        let scores = tree.search_at(&cursor, &config, 0, 1);
+        assert_eq!(scores[0].n, na);
+        tree.search_at(&cursor, &config, 1000, 2);
+        // playouts below the cursor are backed up to the root too
+        let scores = tree.search(&config, 0, 1);
+        assert_eq!(scores[0].n, 4000.0);
+        assert_eq!(
+            scores.iter().find(|s| s.action == 5).unwrap().na,
+            na + 1000.0
+        );
+        // nodes that were never visited are expanded on the way
+        let deep: board::Board = "5 6 7 8 9".parse().unwrap();
+        assert_eq!(tree.cursor(&deep).unwrap().board(), &deep);
+
+        // a corner other than 0 follows the child of 0, and the scores come back in the
+        // orientation of the board that was asked for
+        let corner = scores.iter().find(|s| s.action == 0).unwrap().na;
+        let b: board::Board = "15 0".parse().unwrap();
+        let cursor = tree.cursor(&board::Board::new().next(15)).unwrap();
+        assert_eq!(tree.search_at(&cursor, &config, 0, 1)[0].n, corner);
+        let cursor = tree.cursor(&b).unwrap();
+        assert_eq!(cursor.board(), &b);
+        let scores = tree.search_at(&cursor, &config, 500, 1);
+        assert!(scores.iter().all(|s| b.try_next(s.action).is_ok()));
+        // the diagonal through 0 and 15 is still a mirror line, so moves pair up across it
+        assert!(scores.len() < 16);
+        let keys: Vec<u64> = scores
+            .iter()
+            .map(|s| b.next(s.action).canonical_key())
+            .collect();
+        assert!((1..keys.len()).all(|i| !keys[..i].contains(&keys[i])));
+
+        let tree = mcts::Tree::new(board::Board::new().next(5));
+        assert!(tree.cursor(&board::Board::new().next(6)).is_none());
+        assert!(tree.cursor(&board::Board::new()).is_none());
+
+        let mut record = board::Record::new();
+        record.push(5).unwrap();
+        record.run_mcts_evaluate(2000);
+        record.push(6).unwrap();
+        let scores = record.run_mcts_evaluate(5000);
+        assert!(scores[0].n > 5000.0);
+        // stepping back finds the playouts made after the move counted at the parent
+        record.back();
+        let scores = record.run_mcts_evaluate(0);
+        assert_eq!(scores[0].n, 7000.0);
+        assert!(scores[0].n >= scores.iter().map(|s| s.na).sum::<f32>());
+        assert!(scores.iter().all(|s| s.score <= 1.0));
+
+        // the record's tree stays within its node limit
+        let mut record = board::Record::new();
+        record.set_mcts_nodes(500);
+        record.run_mcts_evaluate(2000);
+        let (tree, _) = record.mcts_tree();
+        assert_eq!(tree.capacity(), 500);
+        assert!(tree.len() <= 500);
+        record.set_mcts_nodes(board::RECORD_TREE_NODES);
+        assert_eq!(record.mcts_tree().0.capacity(), board::RECORD_TREE_NODES);
+
+        let result = board::play_result(
+            &board::Agent::Mcts(mcts::MctsConfig::default(), 300),
+            &board::Agent::Random,
+        );
+        assert_ne!(result, board::GameResult::Ongoing);
+    }
+
+    #[test]
+    fn mcts_config_test() {
+        let config: mcts::MctsConfig = serde_json::from_str(r#"{"selection": "Puct"}"#).unwrap();
+        assert_eq!(config.selection, mcts::Selection::Puct);
+        assert_eq!(config.expand_n, 50);
+        assert_eq!(config.to_string(), "Puct(1.41)/50");
+
+        // Black threatens 3; every formula finds the block. BestValue is left out: the moves
+        // that lose at once get too few random playouts for their Q to mean anything
+        let b: board::Board = "0 4 1 5 2".parse().unwrap();
+        for selection in [
+            mcts::Selection::Ucb1,
+            mcts::Selection::Ucb1Tuned,
+            mcts::Selection::Puct,
+        ] {
+            for final_move in [mcts::FinalMove::MostVisits, mcts::FinalMove::Secure] {
+                let config = mcts::MctsConfig {
+                    selection: selection,
+                    final_move: final_move,
+                    expand_n: 10,
+                    fpu: 0.5,
+                    ..mcts::MctsConfig::default()
+                };
+                assert_eq!(board::mcts_action(&b, &config, 3000), 3, "{}", config);
+            }
+        }
+
+        let score = |action: u8, q: f32, na: f32, proof: mcts::Proof| mcts::Score {
+            action: action,
+            score: na / 1000.0,
+            q: q,
+            na: na,
+            n: 1000.0,
+            proof: proof,
+        };
+        let scores = vec![
+            score(0, 0.1, 900.0, mcts::Proof::Unknown),
+            score(1, 0.3, 80.0, mcts::Proof::Unknown),
+            score(2, 0.6, 4.0, mcts::Proof::Unknown),
+            score(3, 0.0, 0.0, mcts::Proof::Unknown),
+            score(4, 1.0, 15.0, mcts::Proof::Loss),
+        ];
+        let rule = |final_move: mcts::FinalMove| mcts::MctsConfig {
+            final_move: final_move,
+            ..mcts::MctsConfig::default()
+        };
+        assert_eq!(
+            rule(mcts::FinalMove::MostVisits).final_action(&scores),
+            Some(0)
+        );
+        assert_eq!(
+            rule(mcts::FinalMove::BestValue).final_action(&scores),
+            Some(2)
+        );
+        assert_eq!(rule(mcts::FinalMove::Secure).final_action(&scores), Some(1));
+        // a move without playouts is not taken for its Q of 0
+        let unvisited = vec![
+            score(0, -0.2, 500.0, mcts::Proof::Unknown),
+            score(1, 0.0, 0.0, mcts::Proof::Unknown),
+        ];
+        assert_eq!(
+            rule(mcts::FinalMove::BestValue).final_action(&unvisited),
+            Some(0)
+        );
+        assert_eq!(rule(mcts::FinalMove::BestValue).final_action(&[]), None);
+
+        // the only block gets nearly all of the prior, the moves that lose at once nearly none
+        let actions: Vec<u8> = b.actions().collect();
+        let priors = mcts::priors(&b, &actions);
+        let sum: f32 = priors.iter().sum();
+        assert!((sum - 1.0).abs() < 1e-4);
+        for (action, prior) in actions.iter().zip(priors.iter()) {
+            if *action == 3 {
+                assert!(*prior > 0.99);
+            } else {
+                assert!(*prior < 0.01);
+            }
+        }
+        // no legal move makes a playable threat here, so the evaluation alone decides
+        let b: board::Board = "5".parse().unwrap();
+        let actions: Vec<u8> = b.actions().collect();
+        let priors = mcts::priors(&b, &actions);
+        assert!(priors.iter().all(|p| *p > 0.0 && *p < 0.5));
+    }
+
+    #[test]
+    fn mcts_rave_test() {
+        let config: mcts::MctsConfig =
+            serde_json::from_str(r#"{"rave": {"Equivalence": 500.0}}"#).unwrap();
+        assert_eq!(config.rave, mcts::Rave::Equivalence(500.0));
+        assert_eq!(config.to_string(), "Ucb1(1.41)/50/Equivalence(500.0)");
+
+        let b: board::Board = "0 4 1 5 2".parse().unwrap();
+        for rave in [mcts::Rave::Equivalence(500.0), mcts::Rave::MinMse(0.1)] {
+            let config = mcts::MctsConfig {
+                rave: rave,
+                expand_n: 10,
+                ..mcts::MctsConfig::default()
+            };
+            assert_eq!(board::mcts_action(&b, &config, 3000), 3, "{}", config);
+            let tree = mcts::Tree::new(board::Board::new());
+            let scores = tree.search(&config, 2000, 2);
+            let visits: f32 = scores.iter().map(|s| s.na).sum();
+            assert_eq!(visits, 2000.0);
+        }
+
+        let rave = board::Agent::Mcts(
+            mcts::MctsConfig {
+                rave: mcts::Rave::MinMse(0.1),
+                ..mcts::MctsConfig::default()
+            },
+            300,
+        );
+        let plain = board::Agent::Mcts(mcts::MctsConfig::default(), 300);
+        let (s1, s2) = board::eval(&rave, &plain, 1);
+        assert_eq!(s1 + s2, 1.0);
+    }
+
+    #[test]
+    fn mcts_graph_test() {
+        let mut graph = mcts_graph::Graph::new(1 << 20);
+        let scores = graph.search(&board::Board::new(), 5000);
+        assert_eq!(scores.len(), 3);
+        assert!(graph.len() <= graph.capacity());
+        assert!(scores.iter().all(|s| s.n == 5000.0));
+        let visits: f32 = scores.iter().map(|s| s.na).sum();
+        assert_eq!(visits, 5000.0 - 1.0);
+
+        // move orders and mirror images of a position share its statistics
+        let b1: board::Board = "0 5 2".parse().unwrap();
+        let b2: board::Board = "2 5 0".parse().unwrap();
+        let b3: board::Board = "3 6 1".parse().unwrap();
+        assert!(graph.visits(&b1) > 0);
+        assert_eq!(graph.visits(&b1), graph.visits(&b2));
+        assert_eq!(graph.visits(&b1), graph.visits(&b3));
+
+        // a tiny table keeps replacing entries but still finds the win
+        let b: board::Board = "0 4 1 5 2 8".parse().unwrap();
+        let mut graph = mcts_graph::Graph::new(4096);
+        let scores = graph.search(&b, 5000);
+        assert!(graph.len() <= graph.capacity());
+        let best = scores
+            .iter()
+            .max_by(|a, c| a.na.partial_cmp(&c.na).unwrap())
+            .unwrap();
+        assert_eq!(best.action, 3);
+        assert_eq!(board::mcts_graph_action(&b, 1 << 20, 1000), 3);
+    }
+
     #[test]
     fn bench_mcts() {
         for nums in vec![50, 100, 200, 400, 800, 1600] {
@@ -133,7 +1106,7 @@ pub mod tests {
             step += 1;
             let action = board::get_random(&b);
             b = b.next(action);
-            if b.is_win() || b.is_draw() {
+            if b.result() != board::GameResult::Ongoing {
                 break;
             }
         }
@@ -146,12 +1119,16 @@ pub mod tests {
         let mut time_acum = 0;
         loop {
             time_acum += get_time_nanos(|| {
-                board::mcts_action(&b, num, expand_n);
+                let config = mcts::MctsConfig {
+                    expand_n: expand_n as u32,
+                    ..mcts::MctsConfig::default()
+                };
+                board::mcts_action(&b, &config, num);
             });
             step += 1;
             let action = board::get_random(&b);
             b = b.next(action);
-            if b.is_win() || b.is_draw() {
+            if b.result() != board::GameResult::Ongoing {
                 break;
             }
         }
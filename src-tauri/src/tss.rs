@@ -0,0 +1,118 @@
+use super::board::{Board, MateNode, MateReply};
+use std::collections::HashMap;
+
+// threat-space search: the attacker only plays moves that leave a playable winning cell, so
+// every defender reply but the block loses at once and the tree stays narrow enough to find
+// wins far deeper than the full-width searches. A failure proves nothing about the position.
+pub struct ThreatSpaceSearch {
+    pub nodes: u64,
+    node_limit: u64,
+    stopped: bool,
+    // attacker moves known to be too few from a position, keyed by Board::canonical_key
+    failed: HashMap<u64, u8>,
+}
+
+impl ThreatSpaceSearch {
+    pub fn new(node_limit: u64) -> Self {
+        return ThreatSpaceSearch {
+            nodes: 0,
+            node_limit: node_limit,
+            stopped: false,
+            failed: HashMap::new(),
+        };
+    }
+
+    // a threat sequence winning for the side to move within max_plies, shortest first
+    pub fn solve(&mut self, board: &Board, max_plies: u8) -> Option<MateNode> {
+        if board.is_win() || board.is_full() {
+            return None;
+        }
+        let mut b = board.clone();
+        for moves in 1..=(max_plies + 1) / 2 {
+            if let Some(tree) = self.attack(&mut b, moves) {
+                return Some(tree);
+            }
+            if self.stopped {
+                break;
+            }
+        }
+        return None;
+    }
+
+    // the attacker is to move and has at most `moves` moves left to win
+    fn attack(&mut self, b: &mut Board, moves: u8) -> Option<MateNode> {
+        self.nodes += 1;
+        if self.nodes >= self.node_limit {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return None;
+        }
+        let attacker = b.player();
+        let wins = b.threats(&attacker).playable;
+        if wins != 0 {
+            return Some(MateNode {
+                action: wins.trailing_zeros() as u8 % 16,
+                depth: 1,
+                replies: Vec::new(),
+            });
+        }
+        if moves <= 1 {
+            return None;
+        }
+        let key = b.canonical_key();
+        if self.failed.get(&key).map_or(false, |m| *m >= moves) {
+            return None;
+        }
+
+        // a threat of the defender has to be blocked first, and the block must threaten too
+        let blocks = b.threats(&attacker.next()).playable;
+        let candidates = match blocks.count_ones() {
+            0 => !(b.action_mask() as u16),
+            1 => 1 << (blocks.trailing_zeros() % 16),
+            _ => 0,
+        };
+        for action in b.actions() {
+            if candidates & (1 << action) == 0 {
+                continue;
+            }
+            b.make(action);
+            let replies = if b.threats(&attacker).playable != 0 {
+                self.defend(b, moves)
+            } else {
+                None
+            };
+            b.unmake();
+            if let Some(replies) = replies {
+                return Some(MateNode {
+                    action: action,
+                    depth: replies.iter().map(|r| r.answer.depth + 2).max().unwrap(),
+                    replies: replies,
+                });
+            }
+        }
+        if !self.stopped {
+            self.failed.insert(key, moves);
+        }
+        return None;
+    }
+
+    // the attacker has just made a threat; every reply that does not block it loses at once
+    fn defend(&mut self, b: &mut Board, moves: u8) -> Option<Vec<MateReply>> {
+        let defender = b.player();
+        if b.threats(&defender).playable != 0 {
+            return None;
+        }
+        let mut replies = Vec::new();
+        for reply in b.actions() {
+            b.make(reply);
+            let answer = self.attack(b, moves - 1);
+            b.unmake();
+            replies.push(MateReply {
+                action: reply,
+                answer: answer?,
+            });
+        }
+        return Some(replies);
+    }
+}
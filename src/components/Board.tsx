@@ -14,9 +14,75 @@ const testBoard = "-------------------------------------------------------------
 const coef = 0.01;
 const mctsSearchN = 1000;
 
+type MateNode = {
+    action: number,
+    depth: number,
+    replies: Array<{ action: number, answer: MateNode }>,
+}
+
 type MateRow = {
     depth: number,
     action: number,
+    status: "Proven" | "Disproven" | "Unknown",
+    line: number[],
+    tree: MateNode | null,
+}
+
+type Cell = {
+    layer: number,
+    row: number,
+    column: number,
+}
+
+type GameState = {
+    board: { black: Cell[], white: Cell[], player: string },
+    player: string,
+    ply: number,
+    total_plies: number,
+    legal_moves: Array<{ action: number, layer: number }>,
+    result: string | { Won: string },
+    winning_cells: Cell[],
+    threats: Array<{ cell: Cell, player: string, playable: boolean }>,
+}
+
+const cellIndex = (cell: Cell) => cell.layer * 16 + cell.row * 4 + cell.column;
+
+const toBoardString = (state: GameState) => {
+    let s = Array<string>(64).fill("-");
+    state.board.black.forEach(cell => { s[cellIndex(cell)] = "O" });
+    state.board.white.forEach(cell => { s[cellIndex(cell)] = "X" });
+    return s.join("") + (state.player == "Black" ? "B" : "W");
+}
+
+// drop stones for the given actions onto a board string, alternating players
+const playActions = (board: string, actions: number[]) => {
+    let s = board.split("");
+    for (const action of actions) {
+        for (let layer = 0; layer < 4; layer++) {
+            if (s[layer * 16 + action] == "-") {
+                s[layer * 16 + action] = s[64] == "B" ? "O" : "X";
+                break;
+            }
+        }
+        s[64] = s[64] == "B" ? "W" : "B";
+    }
+    return s.join("");
+}
+
+type Danger = "Safe" | "Unknown" | { Losing: number };
+
+type DangerReport = {
+    threat: MateRow,
+    columns: Array<{ action: number, danger: Danger }>,
+}
+
+const dangerColor = (danger: Danger) => {
+    if (danger === "Safe") {
+        return "lime";
+    } else if (danger === "Unknown") {
+        return "gray";
+    }
+    return "red";
 }
 
 type MctsScoreArray = Array<MctsScore>;
@@ -25,7 +91,8 @@ type MctsScore = {
     score: number,
     q: number,
     na: number,
-    n: number
+    n: number,
+    proof: "Unknown" | "Win" | "Loss" | "Draw",
 }
 
 function Board() {
@@ -35,11 +102,17 @@ function Board() {
     const [rot, setRot] = useState(0);
     const [mouseView, setMouseView] = useState({ x: 0, clicked: false });
     const [board, setBoard] = useState(testBoard);
+    const [winningCells, setWinningCells] = useState<number[]>([]);
 
     const boardSize = (height < width) ? height * 0.5 : width * 0.5;
 
     const [focus, setFocus] = useState<number | null>(null);
-    const [mateRow, setMateRow] = useState<MateRow>({ depth: 0, action: -1 });
+    const [mateRow, setMateRow] = useState<MateRow>({ depth: 0, action: -1, status: "Unknown", line: [], tree: null });
+    const [mateBoard, setMateBoard] = useState(testBoard);
+    const [mateStep, setMateStep] = useState(0);
+    const [mateSolver, setMateSolver] = useState("AlphaBeta");
+    const [mctsSelection, setMctsSelection] = useState("Ucb1");
+    const [danger, setDanger] = useState<DangerReport | null>(null);
     const [mctsRows, setMctsRows] = useState<MctsScoreArray>([]);
     const [intervalMcts, setIntervalMcts] = useState<number | null>(null);
 
@@ -99,12 +172,31 @@ function Board() {
         window.addEventListener('resize', onResize);
     })
 
+    const applyState = (state: GameState) => {
+        setBoard(toBoardString(state));
+        setDanger(null);
+        setWinningCells(state.winning_cells.map(cellIndex));
+        if (typeof state.result === "object") {
+            setMessage(`${state.result.Won} won`);
+        } else if (state.result === "Draw") {
+            setMessage("draw");
+        } else {
+            setMessage(`${state.player} to move (${state.ply}/${state.total_plies})`);
+        }
+        return toBoardString(state);
+    }
+
     async function onClickAction(action: number | null) {
         if (action == null) {
             return;
         }
-        const resBoard: string = await invoke("board_action", { action });
-        setBoard(resBoard);
+        let resBoard: string;
+        try {
+            resBoard = applyState(await invoke("board_action", { action }));
+        } catch (e) {
+            setMessage(e as string);
+            return;
+        }
 
         if (resBoard[action] == "-") {
             setFocus(action);
@@ -124,34 +216,53 @@ function Board() {
     }
 
     async function onClickNext() {
-        setBoard(await invoke("board_next"));
+        applyState(await invoke("board_next"));
     }
 
     async function onClickBack() {
-        setBoard(await invoke("board_back"));
+        applyState(await invoke("board_back"));
     }
 
     async function onClickInit() {
-        setBoard(await invoke("board_init"));
+        applyState(await invoke("board_init"));
     }
 
     async function onClickLast() {
-        setBoard(await invoke("board_last"));
+        applyState(await invoke("board_last"));
     }
 
     const onClickMate = () => {
-        invoke("search_mate").then(res => {
+        invoke("search_mate", { solver: mateSolver }).then(res => {
             console.log(res);
             let castRes = res as MateRow;
             setMateRow(castRes);
+            setMateBoard(board);
+            setMateStep(0);
         });
     }
 
+    // preview the mating line on the board; the game moves restore the real board
+    const onStepMate = (step: number) => {
+        const next = Math.max(0, Math.min(mateRow.line.length, step));
+        setMateStep(next);
+        setBoard(playActions(mateBoard, mateRow.line.slice(0, next)));
+    }
+
+    async function onClickDanger() {
+        setDanger(await invoke("analyze_danger"));
+    }
+
+    const dangerColors = () => {
+        let colors = Array<string | null>(16).fill(null);
+        danger?.columns.forEach(column => { colors[column.action] = dangerColor(column.danger) });
+        return colors;
+    }
+
     function onClickRunMcts() {
         console.log("hoge");
         const id = setInterval(
             () => {
-                invoke("command_run_mcts", { searchN: mctsSearchN }).then(res => {
+                invoke("command_run_mcts", { searchN: mctsSearchN, config: { selection: mctsSelection } }).then(res => {
                     console.log(res);
                     let castRes = res as MctsScoreArray;
                     setMctsRows(castRes);
@@ -172,10 +283,10 @@ function Board() {
         {/* <Canv3d width={boardSize} height={boardSize} /> */}
         <div className="boardArea">
 
-            <View3d width={boardSize} height={boardSize} board={board} rot={rot} focusIdx={focus}
+            <View3d width={boardSize} height={boardSize} board={board} rot={rot} focusIdx={focus} winningCells={winningCells}
                 onPointerDown={onPointerDown} onPointerMove={onPointerMove} onPointerUp={onPointerUp} />
             <Square width={height - boardSize} height={height - boardSize} rot={rot} focusIdx={focus != null ? focus % 16 : null}
-                setFocusIdx={onChangeFocus} onClickSquare={() => { onClickAction(focus) }} />
+                setFocusIdx={onChangeFocus} onClickSquare={() => { onClickAction(focus) }} dangerColors={dangerColors()} />
         </div>
         {message}
         <button onClick={onClickInit}>{'<<'}</button>
@@ -184,10 +295,31 @@ function Board() {
         <button onClick={onClickLast}>{'>>'}</button>
         <br />
         <div>
+            <select value={mateSolver} onChange={e => setMateSolver(e.target.value)}>
+                <option value="AlphaBeta">alpha-beta</option>
+                <option value="ProofNumber">proof-number</option>
+                <option value="ThreatSpace">threat-space</option>
+            </select>
             <button onClick={onClickMate}>mate</button>
-            <SearchRow row={mateRow} forcusIdx={focus != null ? focus % 16 : null} />
+            <button onClick={() => onStepMate(mateStep - 1)}>{'line <'}</button>
+            <button onClick={() => onStepMate(mateStep + 1)}>{'line >'}</button>
+            <SearchRow row={mateRow} step={mateStep} forcusIdx={focus != null ? focus % 16 : null} />
+        </div>
+        <div>
+            <button onClick={onClickDanger}>danger</button>
+            {danger != null && (danger.threat.action == -1
+                ? "no threat found"
+                : `opponent threatens mate in ${danger.threat.depth} with ${danger.threat.action}`)}
+            {danger?.columns.map(column => typeof column.danger === "object"
+                ? <span key={column.action}> {column.action}: losing in {column.danger.Losing}</span>
+                : <span key={column.action}> {column.action}: {column.danger.toLowerCase()}</span>)}
         </div>
         <div>
+            <select value={mctsSelection} onChange={e => setMctsSelection(e.target.value)}>
+                <option value="Ucb1">UCB1</option>
+                <option value="Ucb1Tuned">UCB1-Tuned</option>
+                <option value="Puct">PUCT</option>
+            </select>
             <button onClick={onClickRunMcts}>eval</button>
             <button onClick={onClickStopMcts} >stop eval</button>
             {(() => {
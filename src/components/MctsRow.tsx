@@ -7,16 +7,25 @@ type MctsRowProps = {
         q: number,
         na: number,
         n: number,
+        proof: "Unknown" | "Win" | "Loss" | "Draw",
     },
     forcusIdx: number | null,
     setFocus: (idx: number) => void,
     onClick: () => void
 }
 
+const proofText = {
+    Unknown: null,
+    Win: "forced win",
+    Loss: "forced loss",
+    Draw: "forced draw",
+};
+
 const MctsRow = (props: MctsRowProps) => {
+    const proof = proofText[props.row.proof];
     return <div onClick={() => { props.onClick() }} onMouseMove={(_) => { props.setFocus(props.row.action) }} className={props.row.action === props.forcusIdx ? "search_row highlight" : "search_row"}>
         <p className="search_result">
-            {props.row.action == -1 ? "None" : `action: ${props.row.action}, score: ${(100 * props.row.score).toFixed(1)}(${props.row.na}/${props.row.n}), q:${props.row.q.toFixed(3)}`}
+            {props.row.action == -1 ? "None" : proof != null ? `action: ${props.row.action}, ${proof}` : `action: ${props.row.action}, score: ${(100 * props.row.score).toFixed(1)}(${props.row.na}/${props.row.n}), q:${props.row.q.toFixed(3)}`}
         </p>
     </div>
 }
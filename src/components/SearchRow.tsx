@@ -1,15 +1,20 @@
 import "./SearchRow.css";
 
 type SearchRowProps = {
-    row: { depth: number, action: number },
+    row: { depth: number, action: number, status: string, line: number[] },
+    step: number,
     forcusIdx: number | null,
 }
 
 const SearchRow = (props: SearchRowProps) => {
     return <div className={props.row.action === props.forcusIdx ? "search_row highlight" : "search_row"}>
         <p className="search_result">
-            {props.row.action == -1 ? "None" : `depth: ${props.row.depth}, action: ${props.row.action}`}
+            {props.row.action == -1 ? (props.row.status === "Disproven" ? "No forced win" : "None") : `depth: ${props.row.depth}, action: ${props.row.action}`}
         </p>
+        {props.row.line.length > 0 &&
+            <p className="search_result">
+                {props.row.line.map((action, i) => i < props.step ? `[${action}]` : `${action}`).join(" ")}
+            </p>}
     </div>
 }
 
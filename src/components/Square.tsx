@@ -8,6 +8,8 @@ type SquareProps = {
     focusIdx: number | null,
     setFocusIdx: (action: number) => void,
     onClickSquare: () => void,
+    // a marker colour per column, or null for no marker
+    dangerColors: Array<string | null>,
 }
 
 function rotation(x: number, y: number, rad: number) {
@@ -67,6 +69,17 @@ function Square(props: SquareProps) {
                 lines.push(<line className="squareLine" x1={pos1[0] + c} y1={pos1[1] + c} x2={pos2[0] + c} y2={pos2[1] + c} />)
                 lines.push(<line className="squareLine" x1={-pos1[1] + c} y1={pos1[0] + c} x2={-pos2[1] + c} y2={pos2[0] + c} />)
             }
+            props.dangerColors.forEach((color, action) => {
+                if (color == null) {
+                    return;
+                }
+                const pos = rotation(
+                    0.7 * w * (1 + -4 + 2 * (action % 4)) / 8,
+                    0.7 * h * (1 + -4 + 2 * Math.floor(action / 4)) / 8,
+                    props.rot
+                )
+                lines.push(<circle cx={pos[0] + c} cy={pos[1] + c} r={16} fill="none" stroke={color} strokeWidth={3} />)
+            });
             if (props.focusIdx != null) {
                 const pos = rotation(
                     0.7 * w * (1 + -4 + 2 * (props.focusIdx % 4)) / 8,
@@ -38,6 +38,7 @@ const White = "white";
 const Green = "green";
 const DarkGreen = "#008800";
 const BrightGreen = "#77ff77";
+const Gold = "#ffcc00";
 
 type BallProps = {
     position: [x: number, y: number, z: number];
@@ -90,7 +91,8 @@ const Lines = () => {
 
 type BallsProps = {
     ball: string,
-    focusIdx: null | number
+    focusIdx: null | number,
+    winningCells: number[]
 }
 
 function Balls(props: BallsProps) {
@@ -99,7 +101,9 @@ function Balls(props: BallsProps) {
         const x = i % 4;
         const y = Math.floor(i / 16);
         const z = Math.floor((i - y * 16) / 4);
-        if (props.ball[i] == "O") {
+        if (props.winningCells.includes(i)) {
+            list.push(<Ball position={[x * 2 - 3, y * 2 - 3, z * 2 - 3]} color={Gold} />)
+        } else if (props.ball[i] == "O") {
             list.push(<Ball position={[x * 2 - 3, y * 2 - 3, z * 2 - 3]} color={Black} />)
         } else if (props.ball[i] == "X") {
             list.push(<Ball position={[x * 2 - 3, y * 2 - 3, z * 2 - 3]} color={White} />)
@@ -123,6 +127,7 @@ type CanvasProps = {
     board: string,
     rot: number,
     focusIdx: null | number,
+    winningCells: number[],
     onPointerDown: React.PointerEventHandler<HTMLDivElement>,
     onPointerUp: React.PointerEventHandler<HTMLDivElement>,
     onPointerMove: React.PointerEventHandler<HTMLDivElement>
@@ -137,7 +142,7 @@ function View3d(props: CanvasProps) {
             <directionalLight intensity={3} position={[10, 10, 10]} />
             <Rig rad={props.rot} />
             <pointLight position={[-10, -10, -10]} />
-            <Balls ball={props.board} focusIdx={props.focusIdx} />
+            <Balls ball={props.board} focusIdx={props.focusIdx} winningCells={props.winningCells} />
             <Plane position={[0, -3.8, 0]} rotation={[-Math.PI / 2, 0, 0]} args={[10, 10]} receiveShadow>
                 <meshStandardMaterial color="#f55" />
             </Plane>